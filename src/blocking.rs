@@ -0,0 +1,212 @@
+use std::{
+    collections::VecDeque,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    thread,
+    time::Duration,
+};
+
+use tracing::{instrument, trace};
+
+use crate::{
+    job::{HeapJob, Job, JobRef, JobResult},
+    latch::Latch,
+    ThreadWoker,
+};
+
+// 阻塞任务使用的辅助线程池，和计算线程池分开：线程按需创建，最多 max_threads 个，空闲超过 keep_alive 后退出
+#[derive(Debug, Clone, Copy)]
+pub struct BlockingConfig {
+    pub max_threads: usize,
+    pub keep_alive: Duration,
+}
+
+impl Default for BlockingConfig {
+    fn default() -> Self {
+        BlockingConfig {
+            max_threads: 512,
+            keep_alive: Duration::from_secs(10),
+        }
+    }
+}
+
+struct BlockingState {
+    queue: VecDeque<JobRef>,
+    threads: usize,
+    idle: usize,
+    config: BlockingConfig,
+}
+
+struct BlockingPool {
+    state: Mutex<BlockingState>,
+    condvar: Condvar,
+}
+
+static BLOCKING_POOL: OnceLock<BlockingPool> = OnceLock::new();
+
+fn blocking_pool() -> &'static BlockingPool {
+    BLOCKING_POOL.get_or_init(|| BlockingPool {
+        state: Mutex::new(BlockingState {
+            queue: VecDeque::new(),
+            threads: 0,
+            idle: 0,
+            config: BlockingConfig::default(),
+        }),
+        condvar: Condvar::new(),
+    })
+}
+
+impl BlockingPool {
+    // 没有空闲线程并且没有达到上限时创建新线程，否则排队等待
+    fn push(&'static self, job: JobRef) {
+        let mut state = self.state.lock().unwrap();
+        state.queue.push_back(job);
+
+        if state.idle >= state.queue.len() || state.threads >= state.config.max_threads {
+            self.condvar.notify_one();
+            return;
+        }
+
+        state.threads += 1;
+        trace!("spawn blocking thread: {}", state.threads);
+        drop(state);
+
+        thread::spawn(move || self.thread_loop());
+    }
+
+    fn thread_loop(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                unsafe { job.execute() };
+                state = self.state.lock().unwrap();
+                continue;
+            }
+
+            let keep_alive = state.config.keep_alive;
+            state.idle += 1;
+            let (guard, timeout) = self.condvar.wait_timeout(state, keep_alive).unwrap();
+            state = guard;
+            state.idle -= 1;
+
+            if timeout.timed_out() && state.queue.is_empty() {
+                state.threads -= 1;
+                trace!("blocking thread exit: {}", state.threads);
+                return;
+            }
+        }
+    }
+}
+
+// 修改阻塞线程池的配置，只影响之后创建的线程和之后的空闲等待
+pub fn set_blocking_config(config: BlockingConfig) {
+    assert!(config.max_threads > 0, "max_threads must not be zero");
+
+    blocking_pool().state.lock().unwrap().config = config;
+}
+
+// 当前阻塞线程池中的线程数
+pub fn blocking_threads() -> usize {
+    blocking_pool().state.lock().unwrap().threads
+}
+
+// 在阻塞线程池中执行 func，不占用计算线程池的 worker，适合阻塞的文件 I/O
+#[instrument(skip_all)]
+pub fn spawn_blocking<F, R>(func: F) -> BlockingHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let shared = Arc::new(BlockingShared {
+        result: Mutex::new(None),
+        condvar: Condvar::new(),
+    });
+    let sender = shared.clone();
+
+    let job = HeapJob::new(move || {
+        let result = catch_unwind(AssertUnwindSafe(func));
+
+        *sender.result.lock().unwrap() = Some(result);
+        sender.condvar.notify_all();
+    });
+
+    unsafe {
+        blocking_pool().push(job.into_job_ref());
+    }
+
+    BlockingHandle { shared }
+}
+
+// 在 worker 上执行会阻塞的 func：func 交给阻塞线程池执行，当前 worker 在等待期间继续执行其它 job，
+// 计算线程池的并行度不会下降；不在 worker 上时直接执行
+#[instrument(skip_all)]
+pub fn block_in_place<F, R>(func: F) -> R
+where
+    F: FnOnce() -> R + Send,
+    R: Send,
+{
+    let worker = ThreadWoker::current();
+
+    if worker.is_null() {
+        return func();
+    }
+
+    unsafe {
+        let worker = &*worker;
+
+        // 确定性调度的线程池中所有的 job 必须串行执行
+        if worker.root.scheduler.is_some() {
+            return func();
+        }
+
+        let latch = Arc::new(Latch::new());
+        let mut result = JobResult::None;
+        let job = Job::new(func, latch.clone(), &mut result);
+
+        // job 在栈上，返回之前必须等到它执行完
+        blocking_pool().push(JobRef::new(&job));
+
+        while !latch.probe() {
+            if let Some(job) = worker.pop().or_else(|| worker.root.find_work(worker.index)) {
+                job.execute();
+            } else {
+                thread::yield_now();
+            }
+        }
+
+        result.into_return_value()
+    }
+}
+
+struct BlockingShared<R> {
+    result: Mutex<Option<thread::Result<R>>>,
+    condvar: Condvar,
+}
+
+pub struct BlockingHandle<R> {
+    shared: Arc<BlockingShared<R>>,
+}
+
+impl<R> BlockingHandle<R> {
+    pub fn is_finished(&self) -> bool {
+        self.shared.result.lock().unwrap().is_some()
+    }
+
+    // 等待 func 结束并返回结果，func panic 时在这里重新抛出
+    pub fn join(self) -> R {
+        let mut result = self.shared.result.lock().unwrap();
+
+        loop {
+            match result.take() {
+                Some(Ok(r)) => return r,
+                Some(Err(err)) => {
+                    drop(result);
+                    resume_unwind(err)
+                }
+                None => result = self.shared.condvar.wait(result).unwrap(),
+            }
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    cell::RefCell,
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use tracing::{instrument, trace};
+
+use crate::{
+    iter::vec::{Consumer, FromParallelIterator, ParallelIterator},
+    join,
+};
+
+// 协作式的取消：取消后，还没有开始的拆分直接跳过，正在执行的叶子在下一个 item 之前停止
+// token 通过线程局部变量传递，join 创建的 job 会捕获当前的 token，在其它线程上执行时重新设置
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("operation was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+thread_local! {
+    static CURRENT_TOKEN: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn current_token() -> Option<CancellationToken> {
+    CURRENT_TOKEN.with(|token| token.borrow().clone())
+}
+
+pub(crate) fn is_cancelled() -> bool {
+    CURRENT_TOKEN.with(|token| {
+        token
+            .borrow()
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    })
+}
+
+// 设置当前线程的 token，drop 时恢复原来的 token，panic 时也能正确恢复
+pub(crate) struct TokenGuard {
+    prev: Option<CancellationToken>,
+}
+
+impl TokenGuard {
+    pub(crate) fn set(token: Option<CancellationToken>) -> Self {
+        let prev = CURRENT_TOKEN.with(|current| current.replace(token));
+
+        TokenGuard { prev }
+    }
+}
+
+impl Drop for TokenGuard {
+    fn drop(&mut self) {
+        CURRENT_TOKEN.with(|current| *current.borrow_mut() = self.prev.take());
+    }
+}
+
+// 在 token 的作用域中执行 op，op 中所有的 join 和并行迭代器都会检查这个 token
+// token 在 op 开始前或者执行过程中被取消时返回 Err(Cancelled)，op 的结果（可能是不完整的）会被丢弃
+// 被取消的并行迭代器不会 panic，只返回不完整但有效的结果（例如 collect 得到的 vec 不包含新的元素）
+#[instrument(skip_all)]
+pub fn install_with_token<OP, R>(token: &CancellationToken, op: OP) -> Result<R, Cancelled>
+where
+    OP: FnOnce() -> R,
+{
+    if token.is_cancelled() {
+        return Err(Cancelled);
+    }
+
+    let result = {
+        let _guard = TokenGuard::set(Some(token.clone()));
+        op()
+    };
+
+    if token.is_cancelled() {
+        trace!("operation cancelled");
+        Err(Cancelled)
+    } else {
+        Ok(result)
+    }
+}
+
+// 当前 token 已经取消时不再执行 a / b，递归的算法可以据此尽快结束
+#[instrument(skip_all)]
+pub fn join_cancellable<F1, F2, R1, R2>(a: F1, b: F2) -> Result<(R1, R2), Cancelled>
+where
+    F1: FnOnce() -> R1 + Send,
+    F2: FnOnce() -> R2 + Send,
+    R1: Send,
+    R2: Send,
+{
+    if is_cancelled() {
+        return Err(Cancelled);
+    }
+
+    let result = join(a, b);
+
+    if is_cancelled() {
+        Err(Cancelled)
+    } else {
+        Ok(result)
+    }
+}
+
+// with_cancel 返回的包装，只能通过 for_each / collect 得到 Result，
+// 需要 map 等操作时在 with_cancel 之前串联
+pub struct WithCancel<'t, I> {
+    iter: I,
+    token: &'t CancellationToken,
+}
+
+impl<'t, I> WithCancel<'t, I> {
+    pub(crate) fn new(iter: I, token: &'t CancellationToken) -> Self {
+        WithCancel { iter, token }
+    }
+}
+
+impl<'t, I> WithCancel<'t, I>
+where
+    I: ParallelIterator,
+{
+    pub fn for_each<OP>(self, op: OP) -> Result<(), Cancelled>
+    where
+        OP: Fn(I::Item) + Send + Sync,
+    {
+        let iter = self.iter;
+
+        install_with_token(self.token, move || iter.for_each(op))
+    }
+
+    pub fn collect<C>(self) -> Result<C, Cancelled>
+    where
+        C: FromParallelIterator<I::Item>,
+    {
+        let iter = self.iter;
+
+        install_with_token(self.token, move || iter.collect())
+    }
+}
+
+// run / run_unindexed / par_bridge 在开始时用它包装 consumer，取消后 full 返回 true，还没有开始的拆分会被跳过
+pub(crate) struct CancelConsumer<C> {
+    base: C,
+    token: Option<CancellationToken>,
+}
+
+impl<C> CancelConsumer<C> {
+    pub(crate) fn new(base: C) -> Self {
+        CancelConsumer {
+            base,
+            token: current_token(),
+        }
+    }
+
+    fn cancelled(&self) -> bool {
+        self.token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+}
+
+impl<T, C> Consumer<T> for CancelConsumer<C>
+where
+    C: Consumer<T>,
+    T: Send,
+{
+    type Output = C::Output;
+    type Reducer = C::Reducer;
+
+    fn consume(mut self, item: T) -> Self {
+        if !self.cancelled() {
+            self.base = self.base.consume(item);
+        }
+
+        self
+    }
+
+    fn consume_iter<I>(mut self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let token = self.token.clone();
+        let items = iter
+            .into_iter()
+            .take_while(|_| !token.as_ref().is_some_and(CancellationToken::is_cancelled));
+
+        self.base = self.base.consume_iter(items);
+        self
+    }
+
+    fn complete(self) -> Self::Output {
+        self.base.complete()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left, right, reducer) = self.base.split_at(index);
+
+        (
+            CancelConsumer {
+                base: left,
+                token: self.token.clone(),
+            },
+            CancelConsumer {
+                base: right,
+                token: self.token,
+            },
+            reducer,
+        )
+    }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn full(&self) -> bool {
+        self.cancelled() || self.base.full()
+    }
+}
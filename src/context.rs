@@ -0,0 +1,86 @@
+use std::{any::Any, cell::RefCell, sync::Arc};
+
+#[cfg(feature = "tracing")]
+use tracing::{span::EnteredSpan, Span};
+
+type TaskContext = Arc<dyn Any + Send + Sync>;
+
+thread_local! {
+    static CURRENT_CONTEXT: RefCell<Option<TaskContext>> = const { RefCell::new(None) };
+}
+
+// 在 op 中设置任务上下文，开启 tracing feature 时 op 中的 join / spawn / 并行迭代器创建的 job 都会带上这个值，
+// 即使 job 被其它 worker 窃取，也能通过 task_context 拿到
+pub fn with_task_context<T, OP, R>(value: T, op: OP) -> R
+where
+    T: Any + Send + Sync,
+    OP: FnOnce() -> R,
+{
+    let prev = CURRENT_CONTEXT.with(|current| current.replace(Some(Arc::new(value))));
+    let _guard = LocalGuard { prev };
+
+    op()
+}
+
+// 当前任务上下文的值，没有设置或者类型不是 T 时返回 None
+pub fn task_context<T>() -> Option<Arc<T>>
+where
+    T: Any + Send + Sync,
+{
+    CURRENT_CONTEXT
+        .with(|current| current.borrow().clone())
+        .and_then(|value| value.downcast().ok())
+}
+
+struct LocalGuard {
+    prev: Option<TaskContext>,
+}
+
+impl Drop for LocalGuard {
+    fn drop(&mut self) {
+        CURRENT_CONTEXT.with(|current| *current.borrow_mut() = self.prev.take());
+    }
+}
+
+// job 创建时捕获提交方的 span 和任务上下文，执行时重新进入，窃取到 job 的 worker 上的日志依然属于提交方的操作
+// 没有开启 tracing feature 时什么都不做
+pub(crate) struct JobContext {
+    #[cfg(feature = "tracing")]
+    span: Span,
+    #[cfg(feature = "tracing")]
+    local: Option<TaskContext>,
+}
+
+impl JobContext {
+    pub(crate) fn capture() -> Self {
+        JobContext {
+            #[cfg(feature = "tracing")]
+            span: Span::current(),
+            #[cfg(feature = "tracing")]
+            local: CURRENT_CONTEXT.with(|current| current.borrow().clone()),
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn enter(&self) -> ContextGuard {
+        let prev = CURRENT_CONTEXT.with(|current| current.replace(self.local.clone()));
+
+        ContextGuard {
+            _local: LocalGuard { prev },
+            _span: self.span.clone().entered(),
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn enter(&self) -> ContextGuard {
+        ContextGuard {}
+    }
+}
+
+// 字段按声明的顺序 drop：先退出 span，再恢复任务上下文
+pub(crate) struct ContextGuard {
+    #[cfg(feature = "tracing")]
+    _span: EnteredSpan,
+    #[cfg(feature = "tracing")]
+    _local: LocalGuard,
+}
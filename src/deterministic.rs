@@ -0,0 +1,108 @@
+use std::{
+    any::Any,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::Mutex,
+};
+
+use tracing::{error, instrument, trace};
+
+use crate::ThreadPool;
+
+// for_each_seed 使用的线程数，固定下来保证同一个 seed 在不同的机器上拆分方式也相同
+const SEED_THREADS: usize = 4;
+
+// 确定性调度：线程池只有一个真正的 worker，所有 job 串行执行，
+// 原本由线程竞争决定的事情（join 的两边谁先执行、从队列中取哪个 job）都由 seed 决定，同一个 seed 总是得到同一个执行顺序
+// num_threads 是对外报告的线程数，并行迭代器按它来拆分
+pub(crate) struct Scheduler {
+    state: Mutex<u64>,
+    num_threads: usize,
+}
+
+impl Scheduler {
+    pub(crate) fn new(seed: u64, num_threads: usize) -> Self {
+        Scheduler {
+            state: Mutex::new(seed),
+            num_threads,
+        }
+    }
+
+    pub(crate) fn num_threads(&self) -> usize {
+        self.num_threads
+    }
+
+    // splitmix64，不依赖外部库的实现，seed 的结果不会随着依赖版本变化
+    fn next(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // join 是否先执行 b，模拟 b 被其它 worker 窃取后先于 a 执行
+    // 确定性调度的线程池只有一个 worker，join_serial 也不把 job 放进队列，Root::steal 永远取不到 job，
+    // 窃取带来的顺序变化全部由这里的交换代替
+    pub(crate) fn swap_join(&self) -> bool {
+        self.next() & 1 == 1
+    }
+
+    // 从 len 个等待中的 job 中选一个
+    pub(crate) fn pick(&self, len: usize) -> usize {
+        (self.next() % len as u64) as usize
+    }
+}
+
+// 在 seed 决定的顺序下串行执行 a / b，panic 的处理和 join 一样：两边都执行完后再抛出，a 的 panic 优先
+pub(crate) fn join_serial<F1, F2, R1, R2>(scheduler: &Scheduler, a: F1, b: F2) -> (R1, R2)
+where
+    F1: FnOnce() -> R1,
+    F2: FnOnce() -> R2,
+{
+    let (r1, r2) = if scheduler.swap_join() {
+        trace!("deterministic join: b first");
+        let r2 = catch_unwind(AssertUnwindSafe(b));
+        (catch_unwind(AssertUnwindSafe(a)), r2)
+    } else {
+        let r1 = catch_unwind(AssertUnwindSafe(a));
+        (r1, catch_unwind(AssertUnwindSafe(b)))
+    };
+
+    match (r1, r2) {
+        (Ok(r1), Ok(r2)) => (r1, r2),
+        (Err(err), _) | (_, Err(err)) => resume_unwind(err),
+    }
+}
+
+// 轻量的调度 fuzzer：对每个 seed 创建一个确定性的线程池并在其中执行 op
+// op panic 时报告出错的 seed，之后用 ThreadPool::deterministic 和这个 seed 可以重放同样的执行顺序
+#[instrument(skip_all)]
+pub fn for_each_seed<S, OP>(seeds: S, op: OP)
+where
+    S: IntoIterator<Item = u64>,
+    OP: Fn(u64) + Sync,
+{
+    for seed in seeds {
+        let pool = ThreadPool::deterministic(SEED_THREADS, seed);
+
+        if let Err(err) = catch_unwind(AssertUnwindSafe(|| pool.install(|| op(seed)))) {
+            error!("seed {} failed", seed);
+            panic!(
+                "for_each_seed failed with seed {seed}: {}",
+                panic_message(&*err)
+            );
+        }
+    }
+}
+
+fn panic_message(err: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = err.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = err.downcast_ref::<String>() {
+        msg
+    } else {
+        "non-string panic payload"
+    }
+}
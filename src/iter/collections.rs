@@ -0,0 +1,44 @@
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, LinkedList};
+
+use tracing::instrument;
+
+use super::vec::{IntoIter, IntoParallelIterator};
+
+// 哈希表、树和链表都无法按下标拆分，这里先把 item（或者引用）移动到一个 Vec 中，再复用 Vec 的并行迭代，
+// 整个过程只移动 item 或者创建引用，不会 clone 任何值
+macro_rules! into_par_vec {
+    ($($t:ty => $item:ty, [$($g:tt)*], [$($w:tt)*];)*) => {
+        $(
+            impl<$($g)*> IntoParallelIterator for $t
+            where
+                $($w)*
+            {
+                type Item = $item;
+                type Iter = IntoIter<$item>;
+
+                #[instrument(skip_all)]
+                fn into_par_iter(self) -> IntoIter<$item> {
+                    self.into_iter().collect::<Vec<_>>().into_par_iter()
+                }
+            }
+        )*
+    };
+}
+
+into_par_vec! {
+    HashMap<K, V, S> => (K, V), [K, V, S], [K: Send, V: Send];
+    &'data HashMap<K, V, S> => (&'data K, &'data V), ['data, K, V, S], [K: Sync, V: Sync];
+    &'data mut HashMap<K, V, S> => (&'data K, &'data mut V), ['data, K, V, S], [K: Sync, V: Send];
+    HashSet<T, S> => T, [T, S], [T: Send];
+    &'data HashSet<T, S> => &'data T, ['data, T, S], [T: Sync];
+    BTreeMap<K, V> => (K, V), [K, V], [K: Send, V: Send];
+    &'data BTreeMap<K, V> => (&'data K, &'data V), ['data, K, V], [K: Sync, V: Sync];
+    &'data mut BTreeMap<K, V> => (&'data K, &'data mut V), ['data, K, V], [K: Sync, V: Send];
+    BTreeSet<T> => T, [T], [T: Send];
+    &'data BTreeSet<T> => &'data T, ['data, T], [T: Sync];
+    BinaryHeap<T> => T, [T], [T: Send];
+    &'data BinaryHeap<T> => &'data T, ['data, T], [T: Sync];
+    LinkedList<T> => T, [T], [T: Send];
+    &'data LinkedList<T> => &'data T, ['data, T], [T: Sync];
+    &'data mut LinkedList<T> => &'data mut T, ['data, T], [T: Send];
+}
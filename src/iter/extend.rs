@@ -0,0 +1,408 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, LinkedList, VecDeque},
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+use tracing::instrument;
+
+use super::vec::{
+    collect, Consumer, FromParallelIterator, IntoParallelIterator, ParallelExtend,
+    ParallelIterator, Reducer,
+};
+
+// 非 Vec 的容器无法预先知道每个 item 写入的位置，所以每个拆分出来的叶子各自收集到一个局部容器中，
+// 再由 ExtendReducer 按从左到右的顺序两两合并
+pub(crate) trait Merge {
+    fn merge(&mut self, other: Self);
+}
+
+pub(crate) struct ExtendConsumer<C, T> {
+    container: C,
+    marker: PhantomData<fn(T)>,
+}
+
+impl<C, T> ExtendConsumer<C, T>
+where
+    C: Default,
+{
+    pub(crate) fn new() -> Self {
+        ExtendConsumer {
+            container: C::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+pub(crate) struct ExtendReducer;
+
+impl<C: Merge> Reducer<C> for ExtendReducer {
+    fn reduce(self, mut left: C, right: C) -> C {
+        left.merge(right);
+        left
+    }
+}
+
+impl<C, T> Consumer<T> for ExtendConsumer<C, T>
+where
+    C: Extend<T> + Merge + Default + Send,
+    T: Send,
+{
+    type Output = C;
+    type Reducer = ExtendReducer;
+
+    fn consume(mut self, item: T) -> Self {
+        self.container.extend(Some(item));
+        self
+    }
+
+    fn consume_iter<I>(mut self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.container.extend(iter);
+        self
+    }
+
+    fn complete(self) -> Self::Output {
+        self.container
+    }
+
+    fn split_at(self, _index: usize) -> (Self, Self, Self::Reducer) {
+        (Self::new(), self, ExtendReducer)
+    }
+}
+
+// 每个叶子对应链表中的一个 Vec，合并时只需要拼接链表
+pub(crate) struct ListVec<T>(LinkedList<Vec<T>>);
+
+impl<T> Default for ListVec<T> {
+    fn default() -> Self {
+        ListVec(LinkedList::new())
+    }
+}
+
+impl<T> Extend<T> for ListVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        match self.0.back_mut() {
+            Some(vec) => vec.extend(iter),
+            None => self.0.push_back(iter.into_iter().collect()),
+        }
+    }
+}
+
+impl<T> Merge for ListVec<T> {
+    fn merge(&mut self, mut other: Self) {
+        self.0.append(&mut other.0);
+    }
+}
+
+pub(crate) fn extend_unindexed<I>(iter: I, vec: &mut Vec<I::Item>)
+where
+    I: ParallelIterator,
+{
+    let ListVec(list) = iter.execute(ExtendConsumer::<ListVec<I::Item>, I::Item>::new());
+
+    vec.reserve(list.iter().map(Vec::len).sum());
+    for mut part in list {
+        vec.append(&mut part);
+    }
+}
+
+fn extend<C, I>(container: &mut C, par_iter: I)
+where
+    I: IntoParallelIterator,
+    C: Extend<I::Item> + Merge + Default + Send,
+{
+    let partial = par_iter
+        .into_par_iter()
+        .execute(ExtendConsumer::<C, I::Item>::new());
+
+    container.merge(partial);
+}
+
+impl<K, V, S> Merge for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn merge(&mut self, other: Self) {
+        self.extend(other);
+    }
+}
+
+impl<T, S> Merge for HashSet<T, S>
+where
+    T: Eq + Hash,
+    S: BuildHasher,
+{
+    fn merge(&mut self, other: Self) {
+        self.extend(other);
+    }
+}
+
+impl<K: Ord, V> Merge for BTreeMap<K, V> {
+    fn merge(&mut self, mut other: Self) {
+        self.append(&mut other);
+    }
+}
+
+impl<T: Ord> Merge for BTreeSet<T> {
+    fn merge(&mut self, mut other: Self) {
+        self.append(&mut other);
+    }
+}
+
+impl<T> Merge for VecDeque<T> {
+    fn merge(&mut self, mut other: Self) {
+        self.append(&mut other);
+    }
+}
+
+impl<T> Merge for LinkedList<T> {
+    fn merge(&mut self, mut other: Self) {
+        self.append(&mut other);
+    }
+}
+
+impl Merge for String {
+    fn merge(&mut self, other: Self) {
+        if self.is_empty() {
+            *self = other;
+        } else {
+            self.push_str(&other);
+        }
+    }
+}
+
+impl<K, V, S> ParallelExtend<(K, V)> for HashMap<K, V, S>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    S: BuildHasher + Default + Send,
+{
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+    {
+        extend(self, par_iter);
+    }
+}
+
+impl<T, S> ParallelExtend<T> for HashSet<T, S>
+where
+    T: Eq + Hash + Send,
+    S: BuildHasher + Default + Send,
+{
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        extend(self, par_iter);
+    }
+}
+
+impl<K, V> ParallelExtend<(K, V)> for BTreeMap<K, V>
+where
+    K: Ord + Send,
+    V: Send,
+{
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+    {
+        extend(self, par_iter);
+    }
+}
+
+impl<T> ParallelExtend<T> for BTreeSet<T>
+where
+    T: Ord + Send,
+{
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        extend(self, par_iter);
+    }
+}
+
+impl<T: Send> ParallelExtend<T> for VecDeque<T> {
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        extend(self, par_iter);
+    }
+}
+
+impl<T: Send> ParallelExtend<T> for LinkedList<T> {
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        extend(self, par_iter);
+    }
+}
+
+impl ParallelExtend<char> for String {
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = char>,
+    {
+        extend(self, par_iter);
+    }
+}
+
+impl<'a> ParallelExtend<&'a str> for String {
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = &'a str>,
+    {
+        extend(self, par_iter);
+    }
+}
+
+impl ParallelExtend<String> for String {
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = String>,
+    {
+        extend(self, par_iter);
+    }
+}
+
+impl ParallelExtend<()> for () {
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = ()>,
+    {
+        par_iter.into_par_iter().for_each(|()| {});
+    }
+}
+
+impl<K, V, S> FromParallelIterator<(K, V)> for HashMap<K, V, S>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    S: BuildHasher + Default + Send,
+{
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+    {
+        collect(par_iter)
+    }
+}
+
+impl<T, S> FromParallelIterator<T> for HashSet<T, S>
+where
+    T: Eq + Hash + Send,
+    S: BuildHasher + Default + Send,
+{
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        collect(par_iter)
+    }
+}
+
+impl<K, V> FromParallelIterator<(K, V)> for BTreeMap<K, V>
+where
+    K: Ord + Send,
+    V: Send,
+{
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+    {
+        collect(par_iter)
+    }
+}
+
+impl<T> FromParallelIterator<T> for BTreeSet<T>
+where
+    T: Ord + Send,
+{
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        collect(par_iter)
+    }
+}
+
+impl<T: Send> FromParallelIterator<T> for VecDeque<T> {
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        collect(par_iter)
+    }
+}
+
+impl<T: Send> FromParallelIterator<T> for LinkedList<T> {
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        collect(par_iter)
+    }
+}
+
+impl FromParallelIterator<char> for String {
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = char>,
+    {
+        collect(par_iter)
+    }
+}
+
+impl<'a> FromParallelIterator<&'a str> for String {
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = &'a str>,
+    {
+        collect(par_iter)
+    }
+}
+
+impl FromParallelIterator<String> for String {
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = String>,
+    {
+        collect(par_iter)
+    }
+}
+
+impl<T: Send> FromParallelIterator<T> for Box<[T]> {
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        Vec::from_par_iter(par_iter).into_boxed_slice()
+    }
+}
+
+impl FromParallelIterator<()> for () {
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = ()>,
+    {
+        collect(par_iter)
+    }
+}
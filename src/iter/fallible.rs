@@ -0,0 +1,493 @@
+use std::{
+    convert::Infallible,
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use tracing::instrument;
+
+use super::vec::{Consumer, FromParallelIterator, IntoParallelIterator, ParallelIterator, Reducer};
+
+// 标准库的 `Try` 还没有稳定，这里为 Option / Result / ControlFlow 提供一个最小的替代
+pub trait Try {
+    type Output;
+    type Residual;
+
+    fn from_output(output: Self::Output) -> Self;
+
+    fn from_residual(residual: Self::Residual) -> Self;
+
+    fn branch(self) -> ControlFlow<Self::Residual, Self::Output>;
+}
+
+impl<T> Try for Option<T> {
+    type Output = T;
+    type Residual = Option<Infallible>;
+
+    fn from_output(output: T) -> Self {
+        Some(output)
+    }
+
+    fn from_residual(_residual: Option<Infallible>) -> Self {
+        None
+    }
+
+    fn branch(self) -> ControlFlow<Option<Infallible>, T> {
+        match self {
+            Some(output) => ControlFlow::Continue(output),
+            None => ControlFlow::Break(None),
+        }
+    }
+}
+
+impl<T, E> Try for Result<T, E> {
+    type Output = T;
+    type Residual = Result<Infallible, E>;
+
+    fn from_output(output: T) -> Self {
+        Ok(output)
+    }
+
+    fn from_residual(residual: Result<Infallible, E>) -> Self {
+        match residual {
+            Err(err) => Err(err),
+        }
+    }
+
+    fn branch(self) -> ControlFlow<Result<Infallible, E>, T> {
+        match self {
+            Ok(output) => ControlFlow::Continue(output),
+            Err(err) => ControlFlow::Break(Err(err)),
+        }
+    }
+}
+
+impl<B, C> Try for ControlFlow<B, C> {
+    type Output = C;
+    type Residual = ControlFlow<B, Infallible>;
+
+    fn from_output(output: C) -> Self {
+        ControlFlow::Continue(output)
+    }
+
+    fn from_residual(residual: ControlFlow<B, Infallible>) -> Self {
+        match residual {
+            ControlFlow::Break(b) => ControlFlow::Break(b),
+        }
+    }
+
+    fn branch(self) -> ControlFlow<ControlFlow<B, Infallible>, C> {
+        match self {
+            ControlFlow::Continue(c) => ControlFlow::Continue(c),
+            ControlFlow::Break(b) => ControlFlow::Break(ControlFlow::Break(b)),
+        }
+    }
+}
+
+// 遇到第一个 None 就停止，其它拆分通过共享的 full 标记尽快结束
+pub struct WhileSome<I> {
+    iter: I,
+}
+
+impl<I> WhileSome<I> {
+    pub(crate) fn new(iter: I) -> Self {
+        WhileSome { iter }
+    }
+}
+
+impl<I, T> ParallelIterator for WhileSome<I>
+where
+    I: ParallelIterator<Item = Option<T>>,
+    T: Send,
+{
+    type Item = T;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        let full = AtomicBool::new(false);
+
+        self.iter.execute(WhileSomeConsumer {
+            base: op,
+            full: &full,
+        })
+    }
+}
+
+struct WhileSomeConsumer<'f, C> {
+    base: C,
+    full: &'f AtomicBool,
+}
+
+impl<'f, T, C> Consumer<Option<T>> for WhileSomeConsumer<'f, C>
+where
+    C: Consumer<T>,
+    T: Send,
+{
+    type Output = C::Output;
+    type Reducer = C::Reducer;
+
+    fn consume(mut self, item: Option<T>) -> Self {
+        match item {
+            Some(item) => self.base = self.base.consume(item),
+            None => self.full.store(true, Ordering::Relaxed),
+        }
+
+        self
+    }
+
+    fn consume_iter<I>(mut self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = Option<T>>,
+    {
+        let full = self.full;
+        let items = iter
+            .into_iter()
+            .take_while(|_| !full.load(Ordering::Relaxed))
+            .map_while(|item| {
+                if item.is_none() {
+                    full.store(true, Ordering::Relaxed);
+                }
+                item
+            });
+
+        self.base = self.base.consume_iter(items);
+        self
+    }
+
+    fn complete(self) -> Self::Output {
+        self.base.complete()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left, right, reducer) = self.base.split_at(index);
+
+        (
+            WhileSomeConsumer {
+                base: left,
+                full: self.full,
+            },
+            WhileSomeConsumer {
+                base: right,
+                full: self.full,
+            },
+            reducer,
+        )
+    }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn full(&self) -> bool {
+        self.full.load(Ordering::Relaxed) || self.base.full()
+    }
+}
+
+// 每个叶子产生一个折叠结果，叶子内部遇到 break 时停止
+pub struct TryFold<I, ID, F> {
+    iter: I,
+    identity: ID,
+    fold_op: F,
+}
+
+impl<I, ID, F> TryFold<I, ID, F> {
+    pub(crate) fn new(iter: I, identity: ID, fold_op: F) -> Self {
+        TryFold {
+            iter,
+            identity,
+            fold_op,
+        }
+    }
+}
+
+impl<I, U, ID, F, R> ParallelIterator for TryFold<I, ID, F>
+where
+    I: ParallelIterator,
+    ID: Fn() -> U + Sync,
+    F: Fn(U, I::Item) -> R + Sync,
+    R: Try<Output = U> + Send,
+{
+    type Item = R;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        self.iter.execute(TryFoldConsumer {
+            base: op,
+            identity: &self.identity,
+            fold_op: &self.fold_op,
+        })
+    }
+}
+
+struct TryFoldConsumer<'f, C, ID, F> {
+    base: C,
+    identity: &'f ID,
+    fold_op: &'f F,
+}
+
+impl<'f, T, U, C, ID, F, R> Consumer<T> for TryFoldConsumer<'f, C, ID, F>
+where
+    C: Consumer<R>,
+    ID: Fn() -> U + Sync,
+    F: Fn(U, T) -> R + Sync,
+    R: Try<Output = U> + Send,
+    T: Send,
+{
+    type Output = C::Output;
+    type Reducer = C::Reducer;
+
+    fn consume(self, item: T) -> Self {
+        self.consume_iter(Some(item))
+    }
+
+    fn consume_iter<I>(mut self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut acc = R::from_output((self.identity)());
+
+        for item in iter {
+            if self.base.full() {
+                break;
+            }
+
+            match acc.branch() {
+                ControlFlow::Continue(output) => acc = (self.fold_op)(output, item),
+                ControlFlow::Break(residual) => {
+                    acc = R::from_residual(residual);
+                    break;
+                }
+            }
+        }
+
+        self.base = self.base.consume(acc);
+        self
+    }
+
+    fn complete(self) -> Self::Output {
+        self.base.complete()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left, right, reducer) = self.base.split_at(index);
+
+        (
+            TryFoldConsumer {
+                base: left,
+                identity: self.identity,
+                fold_op: self.fold_op,
+            },
+            TryFoldConsumer {
+                base: right,
+                identity: self.identity,
+                fold_op: self.fold_op,
+            },
+            reducer,
+        )
+    }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+#[instrument(skip_all)]
+pub(crate) fn try_reduce<I, T, R, ID, OP>(iter: I, identity: ID, op: OP) -> R
+where
+    I: ParallelIterator<Item = R>,
+    ID: Fn() -> T + Sync,
+    OP: Fn(T, T) -> R + Sync,
+    R: Try<Output = T> + Send,
+{
+    let full = AtomicBool::new(false);
+
+    iter.execute(TryReduceConsumer {
+        identity: &identity,
+        op: &op,
+        full: &full,
+        acc: R::from_output(identity()),
+    })
+}
+
+// 合并两个结果，任意一边是 break 时直接返回 break，并通知其它拆分停止
+fn try_combine<T, R, OP>(op: &OP, full: &AtomicBool, left: R, right: R) -> R
+where
+    OP: Fn(T, T) -> R,
+    R: Try<Output = T>,
+{
+    let result = match (left.branch(), right.branch()) {
+        (ControlFlow::Continue(left), ControlFlow::Continue(right)) => op(left, right),
+        (ControlFlow::Break(residual), _) | (_, ControlFlow::Break(residual)) => {
+            R::from_residual(residual)
+        }
+    };
+
+    match result.branch() {
+        ControlFlow::Continue(output) => R::from_output(output),
+        ControlFlow::Break(residual) => {
+            full.store(true, Ordering::Relaxed);
+            R::from_residual(residual)
+        }
+    }
+}
+
+struct TryReduceConsumer<'f, ID, OP, R> {
+    identity: &'f ID,
+    op: &'f OP,
+    full: &'f AtomicBool,
+    acc: R,
+}
+
+struct TryReduceReducer<'f, OP> {
+    op: &'f OP,
+    full: &'f AtomicBool,
+}
+
+impl<'f, OP, T, R> Reducer<R> for TryReduceReducer<'f, OP>
+where
+    OP: Fn(T, T) -> R,
+    R: Try<Output = T>,
+{
+    fn reduce(self, left: R, right: R) -> R {
+        try_combine(self.op, self.full, left, right)
+    }
+}
+
+impl<'f, ID, OP, T, R> Consumer<R> for TryReduceConsumer<'f, ID, OP, R>
+where
+    ID: Fn() -> T + Sync,
+    OP: Fn(T, T) -> R + Sync,
+    R: Try<Output = T> + Send,
+{
+    type Output = R;
+    type Reducer = TryReduceReducer<'f, OP>;
+
+    fn consume(mut self, item: R) -> Self {
+        self.acc = try_combine(self.op, self.full, self.acc, item);
+        self
+    }
+
+    fn consume_iter<I>(mut self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = R>,
+    {
+        for item in iter {
+            if self.full() {
+                break;
+            }
+
+            self = self.consume(item);
+        }
+
+        self
+    }
+
+    fn complete(self) -> Self::Output {
+        self.acc
+    }
+
+    fn split_at(self, _index: usize) -> (Self, Self, Self::Reducer) {
+        let left = TryReduceConsumer {
+            identity: self.identity,
+            op: self.op,
+            full: self.full,
+            acc: R::from_output((self.identity)()),
+        };
+        let reducer = TryReduceReducer {
+            op: self.op,
+            full: self.full,
+        };
+
+        (left, self, reducer)
+    }
+
+    fn full(&self) -> bool {
+        self.full.load(Ordering::Relaxed)
+    }
+}
+
+impl<C, T, E> FromParallelIterator<Result<T, E>> for Result<C, E>
+where
+    C: FromParallelIterator<T>,
+    T: Send,
+    E: Send,
+{
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = Result<T, E>>,
+    {
+        // 只保存第一个出现的错误，之后 while_some 会让所有拆分尽快停止
+        let saved_error = Mutex::new(None);
+
+        let collection = par_iter
+            .into_par_iter()
+            .map(|item| match item {
+                Ok(item) => Some(item),
+                Err(err) => {
+                    let mut saved = saved_error.lock().unwrap();
+                    if saved.is_none() {
+                        *saved = Some(err);
+                    }
+                    None
+                }
+            })
+            .while_some()
+            .collect();
+
+        match saved_error.into_inner().unwrap() {
+            Some(err) => Err(err),
+            None => Ok(collection),
+        }
+    }
+}
+
+impl<C, T> FromParallelIterator<Option<T>> for Option<C>
+where
+    C: FromParallelIterator<T>,
+    T: Send,
+{
+    #[instrument(skip_all)]
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = Option<T>>,
+    {
+        let found_none = AtomicBool::new(false);
+
+        let collection = par_iter
+            .into_par_iter()
+            .map(|item| {
+                if item.is_none() {
+                    found_none.store(true, Ordering::Relaxed);
+                }
+                item
+            })
+            .while_some()
+            .collect();
+
+        if found_none.load(Ordering::Relaxed) {
+            None
+        } else {
+            Some(collection)
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use super::vec::{Consumer, IndexedParallelIterator, ParallelIterator};
+
+// 每次拆分 consumer 时 clone 一份 item，同一个叶子中的所有 item 共享同一份状态
+pub struct MapWith<I, T, F> {
+    iter: I,
+    item: T,
+    op: F,
+}
+
+impl<I, T, F> MapWith<I, T, F> {
+    pub(crate) fn new(iter: I, item: T, op: F) -> Self {
+        MapWith { iter, item, op }
+    }
+}
+
+impl<I, T, F, R> ParallelIterator for MapWith<I, T, F>
+where
+    I: ParallelIterator,
+    T: Send + Clone,
+    F: Fn(&mut T, I::Item) -> R + Sync,
+    R: Send,
+{
+    type Item = R;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        let consumer = MapWithConsumer {
+            base: op,
+            item: self.item,
+            op: &self.op,
+        };
+
+        self.iter.execute(consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.iter.opt_len()
+    }
+}
+
+impl<I, T, F, R> IndexedParallelIterator for MapWith<I, T, F>
+where
+    I: IndexedParallelIterator,
+    T: Send + Clone,
+    F: Fn(&mut T, I::Item) -> R + Sync,
+    R: Send,
+{
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+struct MapWithConsumer<'f, C, U, F> {
+    base: C,
+    item: U,
+    op: &'f F,
+}
+
+impl<'f, T, U, C, F, R> Consumer<T> for MapWithConsumer<'f, C, U, F>
+where
+    C: Consumer<R>,
+    U: Send + Clone,
+    F: Fn(&mut U, T) -> R + Sync,
+    T: Send,
+    R: Send,
+{
+    type Output = C::Output;
+    type Reducer = C::Reducer;
+
+    fn consume(mut self, item: T) -> Self {
+        let v = (self.op)(&mut self.item, item);
+        self.base = self.base.consume(v);
+        self
+    }
+
+    fn consume_iter<I>(mut self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let op = self.op;
+        let state = &mut self.item;
+        self.base = self
+            .base
+            .consume_iter(iter.into_iter().map(|item| op(state, item)));
+        self
+    }
+
+    fn complete(self) -> Self::Output {
+        self.base.complete()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left, right, reducer) = self.base.split_at(index);
+
+        (
+            MapWithConsumer {
+                base: left,
+                item: self.item.clone(),
+                op: self.op,
+            },
+            MapWithConsumer {
+                base: right,
+                item: self.item,
+                op: self.op,
+            },
+            reducer,
+        )
+    }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+// 每个叶子调用一次 init 创建状态，状态本身不需要 Send / Clone
+pub struct MapInit<I, INIT, F> {
+    iter: I,
+    init: INIT,
+    op: F,
+}
+
+impl<I, INIT, F> MapInit<I, INIT, F> {
+    pub(crate) fn new(iter: I, init: INIT, op: F) -> Self {
+        MapInit { iter, init, op }
+    }
+}
+
+impl<I, INIT, T, F, R> ParallelIterator for MapInit<I, INIT, F>
+where
+    I: ParallelIterator,
+    INIT: Fn() -> T + Sync,
+    F: Fn(&mut T, I::Item) -> R + Sync,
+    R: Send,
+{
+    type Item = R;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        let consumer = MapInitConsumer {
+            base: op,
+            init: &self.init,
+            op: &self.op,
+            state: LeafState(None),
+        };
+
+        self.iter.execute(consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.iter.opt_len()
+    }
+}
+
+impl<I, INIT, T, F, R> IndexedParallelIterator for MapInit<I, INIT, F>
+where
+    I: IndexedParallelIterator,
+    INIT: Fn() -> T + Sync,
+    F: Fn(&mut T, I::Item) -> R + Sync,
+    R: Send,
+{
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+struct MapInitConsumer<'f, C, INIT, F, U> {
+    base: C,
+    init: &'f INIT,
+    op: &'f F,
+    // 第一次 consume 时调用 init 创建，之后的 consume / consume_iter 都复用它，complete 时释放
+    state: LeafState<U>,
+}
+
+// consumer 只在开始 consume 之前被拆分和移动到其它线程，状态创建之后总是在同一个线程上使用和 drop，
+// 所以状态不需要 Send
+struct LeafState<U>(Option<U>);
+
+unsafe impl<U> Send for LeafState<U> {}
+
+impl<'f, T, U, C, INIT, F, R> Consumer<T> for MapInitConsumer<'f, C, INIT, F, U>
+where
+    C: Consumer<R>,
+    INIT: Fn() -> U + Sync,
+    F: Fn(&mut U, T) -> R + Sync,
+    T: Send,
+    R: Send,
+{
+    type Output = C::Output;
+    type Reducer = C::Reducer;
+
+    fn consume(mut self, item: T) -> Self {
+        let state = self.state.0.get_or_insert_with(self.init);
+        let v = (self.op)(state, item);
+        self.base = self.base.consume(v);
+        self
+    }
+
+    fn consume_iter<I>(mut self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let op = self.op;
+        let state = self.state.0.get_or_insert_with(self.init);
+        self.base = self
+            .base
+            .consume_iter(iter.into_iter().map(|item| op(state, item)));
+        self
+    }
+
+    fn complete(self) -> Self::Output {
+        drop(self.state);
+        self.base.complete()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left, right, reducer) = self.base.split_at(index);
+
+        (
+            MapInitConsumer {
+                base: left,
+                init: self.init,
+                op: self.op,
+                state: LeafState(None),
+            },
+            MapInitConsumer {
+                base: right,
+                init: self.init,
+                op: self.op,
+                state: LeafState(None),
+            },
+            reducer,
+        )
+    }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
@@ -1 +1,14 @@
+mod collections;
+mod extend;
+pub mod fallible;
+pub mod map_with;
+pub mod option;
+pub mod par_bridge;
+pub mod range;
+mod scan;
+pub mod slice;
+mod sort;
+pub mod string;
+pub mod unzip;
 pub mod vec;
+pub mod vec_deque;
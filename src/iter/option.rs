@@ -0,0 +1,93 @@
+use std::option;
+
+use tracing::{instrument, trace};
+
+use super::vec::{
+    run, Consumer, IndexedParallelIterator, IntoParallelIterator, ParallelIterator, Splitable,
+};
+
+// Option 最多只有一个 item，run 不会拆分长度小于 2 的 producer，所以 split_at 只需要处理 0 / len 两种情况
+impl<T: Send> IntoParallelIterator for Option<T> {
+    type Item = T;
+    type Iter = IntoIter<T>;
+
+    #[instrument(skip_all)]
+    fn into_par_iter(self) -> IntoIter<T> {
+        IntoIter { opt: self }
+    }
+}
+
+impl<'data, T: Sync + 'data> IntoParallelIterator for &'data Option<T> {
+    type Item = &'data T;
+    type Iter = IntoIter<&'data T>;
+
+    #[instrument(skip_all)]
+    fn into_par_iter(self) -> IntoIter<&'data T> {
+        IntoIter { opt: self.as_ref() }
+    }
+}
+
+impl<'data, T: Send + 'data> IntoParallelIterator for &'data mut Option<T> {
+    type Item = &'data mut T;
+    type Iter = IntoIter<&'data mut T>;
+
+    #[instrument(skip_all)]
+    fn into_par_iter(self) -> IntoIter<&'data mut T> {
+        IntoIter { opt: self.as_mut() }
+    }
+}
+
+pub struct IntoIter<T> {
+    opt: Option<T>,
+}
+
+impl<T: Send> ParallelIterator for IntoIter<T> {
+    type Item = T;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("option parallel iterator execute");
+
+        run(OptionSplitable { opt: self.opt }, op)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<T: Send> IndexedParallelIterator for IntoIter<T> {
+    fn len(&self) -> usize {
+        usize::from(self.opt.is_some())
+    }
+}
+
+struct OptionSplitable<T> {
+    opt: Option<T>,
+}
+
+impl<T: Send> Splitable for OptionSplitable<T> {
+    type Item = T;
+
+    type IntoIter = option::IntoIter<T>;
+
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let none = OptionSplitable { opt: None };
+
+        if mid == 0 {
+            (none, self)
+        } else {
+            (self, none)
+        }
+    }
+
+    fn len(&self) -> usize {
+        usize::from(self.opt.is_some())
+    }
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.opt.into_iter()
+    }
+}
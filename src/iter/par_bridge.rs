@@ -0,0 +1,83 @@
+use std::{iter::Fuse, sync::Mutex};
+
+use tracing::{instrument, trace};
+
+use super::vec::{Consumer, ParallelIterator, Reducer};
+use crate::{cancel::CancelConsumer, current_num_threads, join};
+
+// 每次持有锁时从共享的迭代器中取出的 item 个数
+const BATCH_SIZE: usize = 16;
+
+// 把任意串行的 Iterator 转换为并行迭代器，item 的顺序不做保证
+pub trait ParallelBridge: Sized {
+    fn par_bridge(self) -> IterBridge<Self>;
+}
+
+impl<T> ParallelBridge for T
+where
+    T: Iterator + Send,
+    T::Item: Send,
+{
+    fn par_bridge(self) -> IterBridge<Self> {
+        IterBridge { iter: self }
+    }
+}
+
+pub struct IterBridge<Iter> {
+    iter: Iter,
+}
+
+// 长度未知也无法拆分，所以 opt_len 始终为 None，collect 时走非 indexed 的路径
+impl<Iter> ParallelIterator for IterBridge<Iter>
+where
+    Iter: Iterator + Send,
+    Iter::Item: Send,
+{
+    type Item = Iter::Item;
+
+    #[instrument(skip_all)]
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("iter bridge parallel iterator execute");
+
+        // 迭代器返回 None 后可能还会被其它叶子调用 next，用 fuse 保证之后一直返回 None
+        let shared = Mutex::new(self.iter.fuse());
+
+        bridge(&shared, current_num_threads(), CancelConsumer::new(op))
+    }
+}
+
+// 先把 consumer 拆分成 splits 个叶子，每个叶子循环地从共享的迭代器中批量取出 item
+fn bridge<Iter, C>(shared: &Mutex<Fuse<Iter>>, splits: usize, consumer: C) -> C::Output
+where
+    Iter: Iterator + Send,
+    C: Consumer<Iter::Item>,
+    Iter::Item: Send,
+{
+    if splits > 1 && !consumer.full() {
+        // 非 indexed 的 consumer 不关心拆分的位置
+        let (left, right, reducer) = consumer.split_at(0);
+        let (left, right) = join(
+            || bridge(shared, splits / 2, left),
+            || bridge(shared, splits - splits / 2, right),
+        );
+
+        return reducer.reduce(left, right);
+    }
+
+    let mut consumer = consumer;
+
+    while !consumer.full() {
+        let batch: Vec<_> = shared.lock().unwrap().by_ref().take(BATCH_SIZE).collect();
+
+        if batch.is_empty() {
+            break;
+        }
+
+        consumer = consumer.consume_iter(batch);
+    }
+
+    consumer.complete()
+}
@@ -0,0 +1,279 @@
+use std::{
+    iter::Chain,
+    ops::{Range, RangeInclusive},
+    option,
+};
+
+use tracing::{instrument, trace};
+
+use super::vec::{
+    run, run_unindexed, Consumer, IndexedParallelIterator, IntoParallelIterator, ParallelIterator,
+    Splitable, UnindexedSplitable,
+};
+
+// range 不需要分配内存，直接按中点拆分 start..end
+// 只有长度一定能用 usize 表示的 range 才实现 IndexedParallelIterator：
+// Range 是 u8 / u16 / u32 / usize 以及对应的有符号类型，RangeInclusive 只有 u8 / u16 / i8 / i16
+// 其它类型（例如 u64 / i128 的 range，或者 0..=usize::MAX）长度不超过 usize 时 opt_len 返回 Some，按 indexed 的方式执行；
+// 超过时 opt_len 返回 None，按中点在原始类型上拆分，走非 indexed 的路径
+pub struct Iter<T> {
+    start: T,
+    end: T,
+}
+
+pub struct IterInclusive<T> {
+    start: T,
+    end: T,
+    // 空的 RangeInclusive 表示为 start == end 且 inclusive 为 false
+    inclusive: bool,
+}
+
+struct RangeSplitable<T> {
+    start: T,
+    end: T,
+    inclusive: bool,
+}
+
+macro_rules! range_impl {
+    ($($t:ty => $ut:ty),* $(,)?) => {
+        $(
+            impl IntoParallelIterator for Range<$t> {
+                type Item = $t;
+                type Iter = Iter<$t>;
+
+                #[instrument(skip_all)]
+                fn into_par_iter(self) -> Iter<$t> {
+                    let end = self.end.max(self.start);
+
+                    Iter {
+                        start: self.start,
+                        end,
+                    }
+                }
+            }
+
+            impl IntoParallelIterator for RangeInclusive<$t> {
+                type Item = $t;
+                type Iter = IterInclusive<$t>;
+
+                #[instrument(skip_all)]
+                fn into_par_iter(self) -> IterInclusive<$t> {
+                    let (start, end) = self.clone().into_inner();
+                    // `RangeInclusive` 被迭代过后可能已经耗尽，这里通过 is_empty 判断而不是直接比较 start / end
+                    let inclusive = !self.is_empty();
+
+                    IterInclusive {
+                        start,
+                        end: if inclusive { end } else { start },
+                        inclusive,
+                    }
+                }
+            }
+
+            impl ParallelIterator for Iter<$t> {
+                type Item = $t;
+
+                fn execute<OP>(self, op: OP) -> OP::Output
+                where
+                    OP: Consumer<Self::Item>,
+                {
+                    trace!("range parallel iterator execute");
+
+                    RangeSplitable {
+                        start: self.start,
+                        end: self.end,
+                        inclusive: false,
+                    }
+                    .execute(op)
+                }
+
+                fn opt_len(&self) -> Option<usize> {
+                    range_len(self.end.wrapping_sub(self.start) as $ut as u128, false)
+                }
+            }
+
+            impl ParallelIterator for IterInclusive<$t> {
+                type Item = $t;
+
+                fn execute<OP>(self, op: OP) -> OP::Output
+                where
+                    OP: Consumer<Self::Item>,
+                {
+                    trace!("range inclusive parallel iterator execute");
+
+                    RangeSplitable {
+                        start: self.start,
+                        end: self.end,
+                        inclusive: self.inclusive,
+                    }
+                    .execute(op)
+                }
+
+                fn opt_len(&self) -> Option<usize> {
+                    range_len(
+                        self.end.wrapping_sub(self.start) as $ut as u128,
+                        self.inclusive,
+                    )
+                }
+            }
+
+            impl RangeSplitable<$t> {
+                fn span(&self) -> u128 {
+                    self.end.wrapping_sub(self.start) as $ut as u128
+                }
+
+                fn execute<C>(self, consumer: C) -> C::Output
+                where
+                    C: Consumer<$t>,
+                {
+                    if range_len(self.span(), self.inclusive).is_some() {
+                        run(self, consumer)
+                    } else {
+                        trace!("range is too long for usize, run unindexed");
+                        run_unindexed(self, consumer)
+                    }
+                }
+            }
+
+            // 只在整个 range 的长度能用 usize 表示时使用，拆分出来的部分也一定能表示
+            impl Splitable for RangeSplitable<$t> {
+                type Item = $t;
+
+                type IntoIter = Chain<Range<$t>, option::IntoIter<$t>>;
+
+                fn split_at(self, mid: usize) -> (Self, Self) {
+                    // 只有 inclusive 且 mid 正好等于全部长度时，mid 才会越过 end
+                    if mid as u128 > self.span() {
+                        let right = RangeSplitable {
+                            start: self.end,
+                            end: self.end,
+                            inclusive: false,
+                        };
+
+                        return (self, right);
+                    }
+
+                    let mid = self.start.wrapping_add(mid as $ut as $t);
+
+                    (
+                        RangeSplitable {
+                            start: self.start,
+                            end: mid,
+                            inclusive: false,
+                        },
+                        RangeSplitable {
+                            start: mid,
+                            end: self.end,
+                            inclusive: self.inclusive,
+                        },
+                    )
+                }
+
+                fn len(&self) -> usize {
+                    range_len(self.span(), self.inclusive).expect("range length overflows usize")
+                }
+
+                fn into_iter(self) -> Self::IntoIter {
+                    let last = if self.inclusive { Some(self.end) } else { None };
+
+                    (self.start..self.end).chain(last)
+                }
+            }
+
+            // 长度超过 usize 的 range 在原始类型上按中点拆分，左边一半总是不包含 end
+            impl UnindexedSplitable for RangeSplitable<$t> {
+                type Item = $t;
+
+                fn split(self) -> (Self, Option<Self>) {
+                    let span = self.span();
+                    let offset = if self.inclusive { span - span / 2 } else { span / 2 };
+
+                    if offset == 0 {
+                        return (self, None);
+                    }
+
+                    let mid = self.start.wrapping_add(offset as $ut as $t);
+
+                    (
+                        RangeSplitable {
+                            start: self.start,
+                            end: mid,
+                            inclusive: false,
+                        },
+                        Some(RangeSplitable {
+                            start: mid,
+                            end: self.end,
+                            inclusive: self.inclusive,
+                        }),
+                    )
+                }
+
+                fn fold_with<C>(self, consumer: C) -> C
+                where
+                    C: Consumer<Self::Item>,
+                {
+                    consumer.consume_iter(Splitable::into_iter(self))
+                }
+            }
+        )*
+    };
+}
+
+// 长度一定能用 usize 表示的 range 才能 indexed
+macro_rules! indexed_range_impl {
+    ($iter:ident: $($t:ty),* $(,)?) => {
+        $(
+            impl IndexedParallelIterator for $iter<$t> {
+                fn len(&self) -> usize {
+                    self.opt_len().expect("range length overflows usize")
+                }
+            }
+        )*
+    };
+}
+
+range_impl! {
+    u8 => u8,
+    u16 => u16,
+    u32 => u32,
+    u64 => u64,
+    u128 => u128,
+    usize => usize,
+    i8 => u8,
+    i16 => u16,
+    i32 => u32,
+    i64 => u64,
+    i128 => u128,
+    isize => usize,
+}
+
+indexed_range_impl! {
+    Iter:
+    u8,
+    u16,
+    u32,
+    usize,
+    i8,
+    i16,
+    i32,
+    isize,
+}
+
+indexed_range_impl! {
+    IterInclusive:
+    u8,
+    u16,
+    i8,
+    i16,
+}
+
+// span 是 end - start，inclusive 时再加一；超过 usize 时返回 None
+fn range_len(span: u128, inclusive: bool) -> Option<usize> {
+    let len = usize::try_from(span).ok()?;
+
+    if inclusive {
+        len.checked_add(1)
+    } else {
+        Some(len)
+    }
+}
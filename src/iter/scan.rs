@@ -0,0 +1,209 @@
+use std::{mem, ptr};
+
+use tracing::{instrument, trace};
+
+use super::vec::{
+    Consumer, IndexedParallelIterator, IntoParallelIterator, ParallelIterator, Reducer, SendPtr,
+};
+use crate::cancel;
+
+// 经典的两遍扫描：
+// 1. 每个叶子在自己负责的 [start, start + len) 中写入局部的 inclusive scan，并记录这一块的长度
+// 2. 串行地对每一块的总和（块中最后一个元素）做 exclusive scan，得到每一块的前缀
+// 3. 并行地把前缀合并到除第一块之外的每一块中
+// op 必须满足结合律，identity 是 op 的单位元
+#[instrument(skip_all)]
+pub(crate) fn par_scan<I, ID, OP>(iter: I, identity: ID, op: OP) -> Vec<I::Item>
+where
+    I: IndexedParallelIterator,
+    ID: Fn() -> I::Item + Sync,
+    OP: Fn(&I::Item, &I::Item) -> I::Item + Sync,
+{
+    let len = iter.len();
+    let mut vec = Vec::with_capacity(len);
+
+    let mut result = iter.execute(ScanConsumer {
+        start: SendPtr::new(vec.as_mut_ptr()),
+        len,
+        index: 0,
+        op: &op,
+    });
+
+    let actual = result.initialized_len;
+    // 被取消时写入的元素可能不足 len 个，丢弃已经写入的元素，返回空的 vec，
+    // 由外层的 install_with_token 返回 Err(Cancelled)
+    if actual != len && cancel::is_cancelled() {
+        drop(result);
+        return vec;
+    }
+    assert!(
+        actual == len,
+        "expected {len} total writes, but got {actual}"
+    );
+
+    let blocks = mem::take(&mut result.blocks);
+    // 元素的所有权交还给 vec，之后 op panic 时由 vec 负责释放
+    mem::forget(result);
+    unsafe {
+        vec.set_len(len);
+    }
+
+    trace!("scan {} blocks", blocks.len());
+
+    let mut rest = vec.as_mut_slice();
+    let mut parts = Vec::with_capacity(blocks.len());
+    let mut carry = identity();
+
+    for (i, block) in blocks.into_iter().enumerate() {
+        let (part, tail) = mem::take(&mut rest).split_at_mut(block);
+        rest = tail;
+
+        let Some(last) = part.last() else {
+            continue;
+        };
+        let next = op(&carry, last);
+
+        // 第一块的前缀是 identity，不需要修正
+        if i > 0 {
+            parts.push((part, carry));
+        }
+        carry = next;
+    }
+
+    parts.into_par_iter().for_each(|(part, carry)| {
+        for item in part.iter_mut() {
+            *item = op(&carry, item);
+        }
+    });
+
+    vec
+}
+
+struct ScanConsumer<'f, T, OP> {
+    start: SendPtr<T>,
+    len: usize,
+    index: usize,
+    op: &'f OP,
+}
+
+impl<'f, T, OP> ScanConsumer<'f, T, OP>
+where
+    OP: Fn(&T, &T) -> T,
+{
+    fn write(&mut self, item: T) {
+        assert!(self.index < self.len, "too many values pushed to consumer");
+
+        unsafe {
+            let slot = self.start.0.add(self.index);
+            let value = match self.index {
+                0 => item,
+                _ => (self.op)(&*slot.sub(1), &item),
+            };
+
+            slot.write(value);
+        }
+        self.index += 1;
+    }
+}
+
+// 和 CollectConsumer 一样，panic 时释放已经写入的元素
+impl<'f, T, OP> Drop for ScanConsumer<'f, T, OP> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.start.0, self.index));
+        }
+    }
+}
+
+// blocks 按顺序记录每个叶子写入的长度，合并时直接拼接
+struct ScanResult<T> {
+    start: SendPtr<T>,
+    initialized_len: usize,
+    blocks: Vec<usize>,
+}
+
+unsafe impl<T: Send> Send for ScanResult<T> {}
+
+impl<T> Drop for ScanResult<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
+                self.start.0,
+                self.initialized_len,
+            ));
+        }
+    }
+}
+
+struct ScanReducer;
+
+impl<T> Reducer<ScanResult<T>> for ScanReducer {
+    fn reduce(self, mut left: ScanResult<T>, mut right: ScanResult<T>) -> ScanResult<T> {
+        // 右边不紧挨着左边时说明左边没有写满，右边随 right 一起 drop，最终在 par_scan 中被检查出来
+        if left.start.add(left.initialized_len).0 == right.start.0 {
+            left.initialized_len += mem::replace(&mut right.initialized_len, 0);
+            left.blocks.append(&mut right.blocks);
+        }
+
+        left
+    }
+}
+
+impl<'f, T, OP> Consumer<T> for ScanConsumer<'f, T, OP>
+where
+    T: Send,
+    OP: Fn(&T, &T) -> T + Sync,
+{
+    type Output = ScanResult<T>;
+    type Reducer = ScanReducer;
+
+    fn consume(mut self, item: T) -> Self {
+        self.write(item);
+
+        self
+    }
+
+    fn consume_iter<I>(mut self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for item in iter {
+            self.write(item);
+        }
+
+        self
+    }
+
+    fn complete(self) -> Self::Output {
+        let result = ScanResult {
+            start: self.start,
+            initialized_len: self.index,
+            blocks: vec![self.index],
+        };
+
+        // 所有权转移给 result
+        mem::forget(self);
+
+        result
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        assert!(index <= self.len);
+        assert_eq!(self.index, 0, "split a consumer after consuming");
+
+        let left = ScanConsumer {
+            start: self.start,
+            len: index,
+            index: 0,
+            op: self.op,
+        };
+        let right = ScanConsumer {
+            start: self.start.add(index),
+            len: self.len - index,
+            index: 0,
+            op: self.op,
+        };
+
+        (left, right, ScanReducer)
+    }
+}
@@ -0,0 +1,641 @@
+use core::slice;
+use std::cmp::Ordering;
+
+use tracing::{instrument, trace};
+
+use super::{
+    sort::{par_merge_sort, par_quicksort, par_sort_by_cached_key},
+    vec::{
+        run, Consumer, IndexedParallelIterator, IntoParallelIterator, ParallelIterator, Splitable,
+    },
+};
+
+impl<'data, T: Sync + 'data> IntoParallelIterator for &'data [T] {
+    type Item = &'data T;
+    type Iter = Iter<'data, T>;
+
+    #[instrument(skip_all)]
+    fn into_par_iter(self) -> Iter<'data, T> {
+        Iter { slice: self }
+    }
+}
+
+impl<'data, T: Sync + 'data> IntoParallelIterator for &'data Vec<T> {
+    type Item = &'data T;
+    type Iter = Iter<'data, T>;
+
+    #[instrument(skip_all)]
+    fn into_par_iter(self) -> Iter<'data, T> {
+        Iter { slice: self }
+    }
+}
+
+impl<'data, T: Send + 'data> IntoParallelIterator for &'data mut [T] {
+    type Item = &'data mut T;
+    type Iter = IterMut<'data, T>;
+
+    #[instrument(skip_all)]
+    fn into_par_iter(self) -> IterMut<'data, T> {
+        IterMut { slice: self }
+    }
+}
+
+impl<'data, T: Send + 'data> IntoParallelIterator for &'data mut Vec<T> {
+    type Item = &'data mut T;
+    type Iter = IterMut<'data, T>;
+
+    #[instrument(skip_all)]
+    fn into_par_iter(self) -> IterMut<'data, T> {
+        IterMut { slice: self }
+    }
+}
+
+pub struct Iter<'data, T> {
+    slice: &'data [T],
+}
+
+impl<'data, T: Sync + 'data> ParallelIterator for Iter<'data, T> {
+    type Item = &'data T;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("slice parallel iterator execute");
+
+        run(SliceSplitable { slice: self.slice }, op)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'data, T: Sync + 'data> IndexedParallelIterator for Iter<'data, T> {
+    fn len(&self) -> usize {
+        self.slice.len()
+    }
+}
+
+pub struct IterMut<'data, T> {
+    slice: &'data mut [T],
+}
+
+impl<'data, T: Send + 'data> ParallelIterator for IterMut<'data, T> {
+    type Item = &'data mut T;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("slice mut parallel iterator execute");
+
+        run(SliceMutSplitable { slice: self.slice }, op)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'data, T: Send + 'data> IndexedParallelIterator for IterMut<'data, T> {
+    fn len(&self) -> usize {
+        self.slice.len()
+    }
+}
+
+struct SliceSplitable<'data, T> {
+    slice: &'data [T],
+}
+
+impl<'data, T: Sync + 'data> Splitable for SliceSplitable<'data, T> {
+    type Item = &'data T;
+
+    type IntoIter = slice::Iter<'data, T>;
+
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let (left, right) = self.slice.split_at(mid);
+
+        (
+            SliceSplitable { slice: left },
+            SliceSplitable { slice: right },
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.slice.len()
+    }
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slice.iter()
+    }
+}
+
+struct SliceMutSplitable<'data, T> {
+    slice: &'data mut [T],
+}
+
+impl<'data, T: Send + 'data> Splitable for SliceMutSplitable<'data, T> {
+    type Item = &'data mut T;
+
+    type IntoIter = slice::IterMut<'data, T>;
+
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let (left, right) = self.slice.split_at_mut(mid);
+
+        (
+            SliceMutSplitable { slice: left },
+            SliceMutSplitable { slice: right },
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.slice.len()
+    }
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slice.iter_mut()
+    }
+}
+
+pub trait ParallelSlice<T: Sync> {
+    fn as_parallel_slice(&self) -> &[T];
+
+    fn par_chunks(&self, chunk_size: usize) -> Chunks<'_, T> {
+        assert!(chunk_size != 0, "chunk_size must not be zero");
+
+        Chunks {
+            slice: self.as_parallel_slice(),
+            chunk_size,
+        }
+    }
+
+    fn par_chunks_exact(&self, chunk_size: usize) -> ChunksExact<'_, T> {
+        assert!(chunk_size != 0, "chunk_size must not be zero");
+
+        let slice = self.as_parallel_slice();
+        let (slice, rem) = slice.split_at(slice.len() - slice.len() % chunk_size);
+
+        ChunksExact {
+            slice,
+            rem,
+            chunk_size,
+        }
+    }
+
+    fn par_rchunks(&self, chunk_size: usize) -> RChunks<'_, T> {
+        assert!(chunk_size != 0, "chunk_size must not be zero");
+
+        RChunks {
+            slice: self.as_parallel_slice(),
+            chunk_size,
+        }
+    }
+
+    fn par_windows(&self, window_size: usize) -> Windows<'_, T> {
+        assert!(window_size != 0, "window_size must not be zero");
+
+        Windows {
+            slice: self.as_parallel_slice(),
+            window_size,
+        }
+    }
+}
+
+impl<T: Sync> ParallelSlice<T> for [T] {
+    fn as_parallel_slice(&self) -> &[T] {
+        self
+    }
+}
+
+pub trait ParallelSliceMut<T: Send> {
+    fn as_parallel_slice_mut(&mut self) -> &mut [T];
+
+    fn par_chunks_mut(&mut self, chunk_size: usize) -> ChunksMut<'_, T> {
+        assert!(chunk_size != 0, "chunk_size must not be zero");
+
+        ChunksMut {
+            slice: self.as_parallel_slice_mut(),
+            chunk_size,
+        }
+    }
+
+    // 稳定排序，基于并行归并排序
+    fn par_sort(&mut self)
+    where
+        T: Ord,
+    {
+        par_merge_sort(self.as_parallel_slice_mut(), &T::cmp);
+    }
+
+    fn par_sort_by<F>(&mut self, compare: F)
+    where
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        par_merge_sort(self.as_parallel_slice_mut(), &compare);
+    }
+
+    fn par_sort_by_key<K, F>(&mut self, f: F)
+    where
+        K: Ord,
+        F: Fn(&T) -> K + Sync,
+    {
+        par_merge_sort(self.as_parallel_slice_mut(), &|a: &T, b: &T| {
+            f(a).cmp(&f(b))
+        });
+    }
+
+    // 每个元素只计算一次 key，适合 key 计算开销较大的场景
+    fn par_sort_by_cached_key<K, F>(&mut self, f: F)
+    where
+        T: Sync,
+        K: Ord + Send,
+        F: Fn(&T) -> K + Send + Sync,
+    {
+        par_sort_by_cached_key(self.as_parallel_slice_mut(), f);
+    }
+
+    // 不稳定排序，基于并行快速排序，不需要额外的内存
+    fn par_sort_unstable(&mut self)
+    where
+        T: Ord,
+    {
+        par_quicksort(self.as_parallel_slice_mut(), &T::cmp);
+    }
+
+    fn par_sort_unstable_by<F>(&mut self, compare: F)
+    where
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        par_quicksort(self.as_parallel_slice_mut(), &compare);
+    }
+
+    fn par_sort_unstable_by_key<K, F>(&mut self, f: F)
+    where
+        K: Ord,
+        F: Fn(&T) -> K + Sync,
+    {
+        par_quicksort(self.as_parallel_slice_mut(), &|a: &T, b: &T| {
+            f(a).cmp(&f(b))
+        });
+    }
+}
+
+impl<T: Send> ParallelSliceMut<T> for [T] {
+    fn as_parallel_slice_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+// 所有 chunk 类的 splitable 都以 chunk 为单位计算 len 和 split_at，保证分割点总是落在 chunk 边界上
+pub struct Chunks<'data, T> {
+    slice: &'data [T],
+    chunk_size: usize,
+}
+
+impl<'data, T: Sync + 'data> ParallelIterator for Chunks<'data, T> {
+    type Item = &'data [T];
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("chunks parallel iterator execute");
+
+        run(
+            ChunksSplitable {
+                slice: self.slice,
+                chunk_size: self.chunk_size,
+            },
+            op,
+        )
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'data, T: Sync + 'data> IndexedParallelIterator for Chunks<'data, T> {
+    fn len(&self) -> usize {
+        self.slice.len().div_ceil(self.chunk_size)
+    }
+}
+
+struct ChunksSplitable<'data, T> {
+    slice: &'data [T],
+    chunk_size: usize,
+}
+
+impl<'data, T: Sync + 'data> Splitable for ChunksSplitable<'data, T> {
+    type Item = &'data [T];
+
+    type IntoIter = slice::Chunks<'data, T>;
+
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let elem_index = self.slice.len().min(mid * self.chunk_size);
+        let (left, right) = self.slice.split_at(elem_index);
+
+        (
+            ChunksSplitable {
+                slice: left,
+                chunk_size: self.chunk_size,
+            },
+            ChunksSplitable {
+                slice: right,
+                chunk_size: self.chunk_size,
+            },
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.slice.len().div_ceil(self.chunk_size)
+    }
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slice.chunks(self.chunk_size)
+    }
+}
+
+pub struct ChunksExact<'data, T> {
+    slice: &'data [T],
+    rem: &'data [T],
+    chunk_size: usize,
+}
+
+impl<'data, T> ChunksExact<'data, T> {
+    pub fn remainder(&self) -> &'data [T] {
+        self.rem
+    }
+}
+
+impl<'data, T: Sync + 'data> ParallelIterator for ChunksExact<'data, T> {
+    type Item = &'data [T];
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("chunks exact parallel iterator execute");
+
+        run(
+            ChunksExactSplitable {
+                slice: self.slice,
+                chunk_size: self.chunk_size,
+            },
+            op,
+        )
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'data, T: Sync + 'data> IndexedParallelIterator for ChunksExact<'data, T> {
+    fn len(&self) -> usize {
+        self.slice.len() / self.chunk_size
+    }
+}
+
+struct ChunksExactSplitable<'data, T> {
+    slice: &'data [T],
+    chunk_size: usize,
+}
+
+impl<'data, T: Sync + 'data> Splitable for ChunksExactSplitable<'data, T> {
+    type Item = &'data [T];
+
+    type IntoIter = slice::ChunksExact<'data, T>;
+
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let (left, right) = self.slice.split_at(mid * self.chunk_size);
+
+        (
+            ChunksExactSplitable {
+                slice: left,
+                chunk_size: self.chunk_size,
+            },
+            ChunksExactSplitable {
+                slice: right,
+                chunk_size: self.chunk_size,
+            },
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.slice.len() / self.chunk_size
+    }
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slice.chunks_exact(self.chunk_size)
+    }
+}
+
+pub struct RChunks<'data, T> {
+    slice: &'data [T],
+    chunk_size: usize,
+}
+
+impl<'data, T: Sync + 'data> ParallelIterator for RChunks<'data, T> {
+    type Item = &'data [T];
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("rchunks parallel iterator execute");
+
+        run(
+            RChunksSplitable {
+                slice: self.slice,
+                chunk_size: self.chunk_size,
+            },
+            op,
+        )
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'data, T: Sync + 'data> IndexedParallelIterator for RChunks<'data, T> {
+    fn len(&self) -> usize {
+        self.slice.len().div_ceil(self.chunk_size)
+    }
+}
+
+struct RChunksSplitable<'data, T> {
+    slice: &'data [T],
+    chunk_size: usize,
+}
+
+impl<'data, T: Sync + 'data> Splitable for RChunksSplitable<'data, T> {
+    type Item = &'data [T];
+
+    type IntoIter = slice::RChunks<'data, T>;
+
+    // rchunks 从尾部开始产出，所以前 mid 个 chunk 位于 slice 的末尾
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let elem_index = self.slice.len().saturating_sub(mid * self.chunk_size);
+        let (right, left) = self.slice.split_at(elem_index);
+
+        (
+            RChunksSplitable {
+                slice: left,
+                chunk_size: self.chunk_size,
+            },
+            RChunksSplitable {
+                slice: right,
+                chunk_size: self.chunk_size,
+            },
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.slice.len().div_ceil(self.chunk_size)
+    }
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slice.rchunks(self.chunk_size)
+    }
+}
+
+pub struct Windows<'data, T> {
+    slice: &'data [T],
+    window_size: usize,
+}
+
+impl<'data, T: Sync + 'data> ParallelIterator for Windows<'data, T> {
+    type Item = &'data [T];
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("windows parallel iterator execute");
+
+        run(
+            WindowsSplitable {
+                slice: self.slice,
+                window_size: self.window_size,
+            },
+            op,
+        )
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'data, T: Sync + 'data> IndexedParallelIterator for Windows<'data, T> {
+    fn len(&self) -> usize {
+        (self.slice.len() + 1).saturating_sub(self.window_size)
+    }
+}
+
+struct WindowsSplitable<'data, T> {
+    slice: &'data [T],
+    window_size: usize,
+}
+
+impl<'data, T: Sync + 'data> Splitable for WindowsSplitable<'data, T> {
+    type Item = &'data [T];
+
+    type IntoIter = slice::Windows<'data, T>;
+
+    // 相邻的 window 有重叠，左右两边各自保留完整的 window
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let left_end = self.slice.len().min(mid + self.window_size - 1);
+
+        (
+            WindowsSplitable {
+                slice: &self.slice[..left_end],
+                window_size: self.window_size,
+            },
+            WindowsSplitable {
+                slice: &self.slice[mid..],
+                window_size: self.window_size,
+            },
+        )
+    }
+
+    fn len(&self) -> usize {
+        (self.slice.len() + 1).saturating_sub(self.window_size)
+    }
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slice.windows(self.window_size)
+    }
+}
+
+pub struct ChunksMut<'data, T> {
+    slice: &'data mut [T],
+    chunk_size: usize,
+}
+
+impl<'data, T: Send + 'data> ParallelIterator for ChunksMut<'data, T> {
+    type Item = &'data mut [T];
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("chunks mut parallel iterator execute");
+
+        run(
+            ChunksMutSplitable {
+                slice: self.slice,
+                chunk_size: self.chunk_size,
+            },
+            op,
+        )
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'data, T: Send + 'data> IndexedParallelIterator for ChunksMut<'data, T> {
+    fn len(&self) -> usize {
+        self.slice.len().div_ceil(self.chunk_size)
+    }
+}
+
+struct ChunksMutSplitable<'data, T> {
+    slice: &'data mut [T],
+    chunk_size: usize,
+}
+
+impl<'data, T: Send + 'data> Splitable for ChunksMutSplitable<'data, T> {
+    type Item = &'data mut [T];
+
+    type IntoIter = slice::ChunksMut<'data, T>;
+
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let elem_index = self.slice.len().min(mid * self.chunk_size);
+        let (left, right) = self.slice.split_at_mut(elem_index);
+
+        (
+            ChunksMutSplitable {
+                slice: left,
+                chunk_size: self.chunk_size,
+            },
+            ChunksMutSplitable {
+                slice: right,
+                chunk_size: self.chunk_size,
+            },
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.slice.len().div_ceil(self.chunk_size)
+    }
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slice.chunks_mut(self.chunk_size)
+    }
+}
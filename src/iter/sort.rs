@@ -0,0 +1,280 @@
+use std::{cmp::Ordering, ptr, slice};
+
+use tracing::instrument;
+
+use super::vec::{IntoParallelIterator, ParallelIterator, SendPtr};
+use crate::join;
+
+// 小于这个长度时直接使用标准库的串行排序，继续拆分的收益抵不过调度的开销
+const SORT_CUTOFF: usize = 2000;
+// 合并的两段总长度小于这个值时串行合并
+const MERGE_CUTOFF: usize = 5000;
+
+// 稳定的并行归并排序：左右两半并行排序，再并行地合并到 buf 中，最后整体拷贝回 v
+// 合并过程中只是把 v 中的元素按位拷贝到 buf，v 依然持有所有元素，所以 compare 在任意时刻 panic，
+// v 都是原来元素的一个排列，每个元素只会被 drop 一次
+#[instrument(skip_all)]
+pub(crate) fn par_merge_sort<T, F>(v: &mut [T], compare: &F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    if v.len() <= SORT_CUTOFF {
+        v.sort_by(compare);
+        return;
+    }
+
+    // buf 的 len 始终为 0，drop 时只释放内存，不会 drop 其中按位拷贝的元素
+    let mut buf = Vec::<T>::with_capacity(v.len());
+    merge_sort(v, SendPtr::new(buf.as_mut_ptr()), compare);
+}
+
+fn merge_sort<T, F>(v: &mut [T], buf: SendPtr<T>, compare: &F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let len = v.len();
+
+    if len <= SORT_CUTOFF {
+        v.sort_by(compare);
+        return;
+    }
+
+    let mid = len / 2;
+    let (left, right) = v.split_at_mut(mid);
+    join(
+        move || merge_sort(left, buf, compare),
+        move || merge_sort(right, buf.add(mid), compare),
+    );
+
+    // 两半已经整体有序时不需要合并
+    if compare(&v[mid - 1], &v[mid]) != Ordering::Greater {
+        return;
+    }
+
+    let src = SendPtr::new(v.as_mut_ptr());
+    unsafe {
+        par_merge(src, mid, src.add(mid), len - mid, buf, compare);
+        ptr::copy_nonoverlapping(buf.0, v.as_mut_ptr(), len);
+    }
+}
+
+// 在较长的一段中取中点作为 pivot，二分查找另一段的分割点，把合并拆成互不相交的两个子问题
+// 每个元素只会被一个子任务访问，所以不需要 T: Sync
+unsafe fn par_merge<T, F>(
+    left: SendPtr<T>,
+    left_len: usize,
+    right: SendPtr<T>,
+    right_len: usize,
+    dest: SendPtr<T>,
+    compare: &F,
+) where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let left_slice = slice::from_raw_parts(left.0, left_len);
+    let right_slice = slice::from_raw_parts(right.0, right_len);
+
+    if left_len == 0 || right_len == 0 || left_len + right_len <= MERGE_CUTOFF {
+        merge(left_slice, right_slice, dest.0, compare);
+        return;
+    }
+
+    let (left_mid, right_mid) = if left_len >= right_len {
+        let left_mid = left_len / 2;
+        let pivot = &left_slice[left_mid];
+        // right 中严格小于 pivot 的元素排在前半部分
+        let right_mid = right_slice.partition_point(|x| compare(x, pivot) == Ordering::Less);
+
+        (left_mid, right_mid)
+    } else {
+        let right_mid = right_len / 2;
+        let pivot = &right_slice[right_mid];
+        // left 中小于等于 pivot 的元素排在前半部分，保证相等元素中 left 的在前
+        let left_mid = left_slice.partition_point(|x| compare(x, pivot) != Ordering::Greater);
+
+        (left_mid, right_mid)
+    };
+
+    join(
+        move || par_merge(left, left_mid, right, right_mid, dest, compare),
+        move || {
+            par_merge(
+                left.add(left_mid),
+                left_len - left_mid,
+                right.add(right_mid),
+                right_len - right_mid,
+                dest.add(left_mid + right_mid),
+                compare,
+            )
+        },
+    );
+}
+
+unsafe fn merge<T, F>(left: &[T], right: &[T], mut dest: *mut T, compare: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let (mut l, mut r) = (0, 0);
+
+    while l < left.len() && r < right.len() {
+        // 只有 right 严格小于 left 时才先取 right，保证稳定
+        let src = if compare(&right[r], &left[l]) == Ordering::Less {
+            r += 1;
+            &right[r - 1]
+        } else {
+            l += 1;
+            &left[l - 1]
+        };
+
+        ptr::copy_nonoverlapping(src, dest, 1);
+        dest = dest.add(1);
+    }
+
+    ptr::copy_nonoverlapping(left[l..].as_ptr(), dest, left.len() - l);
+    dest = dest.add(left.len() - l);
+    ptr::copy_nonoverlapping(right[r..].as_ptr(), dest, right.len() - r);
+}
+
+// 不稳定的并行快速排序：取 ninther 作为 pivot，划分后两边并行排序
+// 划分只使用 swap，compare panic 时 v 依然是原来元素的一个排列
+// 划分明显不均衡的次数超过 log(len) 时退回标准库的 pdqsort，保证最坏情况下也是 O(n log n)
+#[instrument(skip_all)]
+pub(crate) fn par_quicksort<T, F>(v: &mut [T], compare: &F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let limit = usize::BITS - v.len().leading_zeros();
+
+    quicksort(v, compare, limit);
+}
+
+fn quicksort<T, F>(v: &mut [T], compare: &F, limit: u32)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let len = v.len();
+
+    if len <= SORT_CUTOFF || limit == 0 {
+        v.sort_unstable_by(compare);
+        return;
+    }
+
+    let pivot = choose_pivot(v, compare);
+    v.swap(0, pivot);
+
+    let (pivot, rest) = v.split_at_mut(1);
+    let pivot = &pivot[0];
+
+    let mid = partition(rest, |x| compare(x, pivot) == Ordering::Less);
+    // 左边很少时，右边可能有大量等于 pivot 的元素，把它们单独划分出来，不再参与后续排序
+    let mut equal = 0;
+    if mid < len / 8 {
+        equal = partition(&mut rest[mid..], |x| compare(x, pivot) != Ordering::Greater);
+    }
+
+    let greater = len - 1 - mid - equal;
+    let limit = if mid.min(greater) < len / 8 {
+        limit - 1
+    } else {
+        limit
+    };
+
+    // 把 pivot 换到小于它的元素之后
+    v.swap(0, mid);
+
+    let (left, right) = v.split_at_mut(mid);
+    let right = &mut right[1 + equal..];
+
+    join(
+        move || quicksort(left, compare, limit),
+        move || quicksort(right, compare, limit),
+    );
+}
+
+// 满足 pred 的元素移到前面，返回满足 pred 的元素个数
+fn partition<T>(v: &mut [T], mut pred: impl FnMut(&T) -> bool) -> usize {
+    let (mut l, mut r) = (0, v.len());
+
+    loop {
+        while l < r && pred(&v[l]) {
+            l += 1;
+        }
+
+        while l < r && !pred(&v[r - 1]) {
+            r -= 1;
+        }
+
+        if l >= r {
+            return l;
+        }
+
+        r -= 1;
+        v.swap(l, r);
+        l += 1;
+    }
+}
+
+fn choose_pivot<T, F>(v: &[T], compare: &F) -> usize
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let len = v.len();
+    let (a, b, c) = (len / 4, len / 2, len / 4 * 3);
+
+    let median3 = |a: usize, b: usize, c: usize| {
+        let (mut a, mut b, mut c) = (a, b, c);
+
+        if compare(&v[b], &v[a]) == Ordering::Less {
+            std::mem::swap(&mut a, &mut b);
+        }
+        if compare(&v[c], &v[b]) == Ordering::Less {
+            std::mem::swap(&mut b, &mut c);
+        }
+        if compare(&v[b], &v[a]) == Ordering::Less {
+            std::mem::swap(&mut a, &mut b);
+        }
+
+        b
+    };
+
+    median3(
+        median3(a - 1, a, a + 1),
+        median3(b - 1, b, b + 1),
+        median3(c - 1, c, c + 1),
+    )
+}
+
+// 先并行计算所有 key，按 (key, index) 排序，再按排序后的下标原地置换 v
+// index 互不相同，所以不稳定排序得到的结果也是稳定的
+#[instrument(skip_all)]
+pub(crate) fn par_sort_by_cached_key<T, K, F>(v: &mut [T], f: F)
+where
+    T: Send + Sync,
+    K: Ord + Send,
+    F: Fn(&T) -> K + Send + Sync,
+{
+    let len = v.len();
+
+    if len < 2 {
+        return;
+    }
+
+    let keys: &[T] = v;
+    let mut indices: Vec<(K, usize)> = (0..len).into_par_iter().map(|i| (f(&keys[i]), i)).collect();
+
+    par_quicksort(&mut indices, &|a: &(K, usize), b: &(K, usize)| a.cmp(b));
+
+    for i in 0..len {
+        let mut index = indices[i].1;
+        // 位置 index 上的元素在之前的置换中已经被换走了，沿着置换链找到它现在的位置
+        while index < i {
+            index = indices[index].1;
+        }
+        indices[i].1 = index;
+        v.swap(i, index);
+    }
+}
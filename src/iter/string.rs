@@ -0,0 +1,459 @@
+use core::slice;
+use std::iter::Copied;
+
+use tracing::{instrument, trace};
+
+use super::vec::{
+    run, run_unindexed, Consumer, IndexedParallelIterator, ParallelIterator, Splitable,
+    UnindexedSplitable,
+};
+
+// 字符串只能在字符边界上拆分，字符个数和分割出的子串个数都无法预先知道，所以除了 par_bytes 之外都是非 indexed 的
+pub trait ParallelString {
+    fn as_parallel_string(&self) -> &str;
+
+    fn par_chars(&self) -> Chars<'_> {
+        Chars {
+            chars: self.as_parallel_string(),
+        }
+    }
+
+    fn par_char_indices(&self) -> CharIndices<'_> {
+        CharIndices {
+            chars: self.as_parallel_string(),
+        }
+    }
+
+    fn par_bytes(&self) -> Bytes<'_> {
+        Bytes {
+            bytes: self.as_parallel_string().as_bytes(),
+        }
+    }
+
+    fn par_split<P: Pattern>(&self, separator: P) -> Split<'_, P> {
+        Split {
+            chars: self.as_parallel_string(),
+            separator,
+        }
+    }
+
+    // 和 par_split 相同，但是字符串以分隔符结尾时不产生最后的空串
+    fn par_split_terminator<P: Pattern>(&self, terminator: P) -> SplitTerminator<'_, P> {
+        SplitTerminator {
+            chars: self.as_parallel_string(),
+            terminator,
+        }
+    }
+
+    // 以 "\n" 或 "\r\n" 分行，和 str::lines 的结果一致
+    fn par_lines(&self) -> Lines<'_> {
+        Lines {
+            chars: self.as_parallel_string(),
+        }
+    }
+
+    fn par_split_whitespace(&self) -> SplitWhitespace<'_> {
+        SplitWhitespace {
+            chars: self.as_parallel_string(),
+        }
+    }
+}
+
+impl ParallelString for str {
+    fn as_parallel_string(&self) -> &str {
+        self
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+// 分隔符只支持单个字符的匹配，这样才能在任意位置向前或向后查找最近的分隔符
+pub trait Pattern: private::Sealed + Sync + Send {
+    #[doc(hidden)]
+    fn is_match(&self, c: char) -> bool;
+}
+
+impl private::Sealed for char {}
+
+impl Pattern for char {
+    fn is_match(&self, c: char) -> bool {
+        *self == c
+    }
+}
+
+impl private::Sealed for &[char] {}
+
+impl Pattern for &[char] {
+    fn is_match(&self, c: char) -> bool {
+        self.contains(&c)
+    }
+}
+
+impl<const N: usize> private::Sealed for [char; N] {}
+
+impl<const N: usize> Pattern for [char; N] {
+    fn is_match(&self, c: char) -> bool {
+        self.contains(&c)
+    }
+}
+
+impl<F> private::Sealed for F where F: Fn(char) -> bool + Sync + Send {}
+
+impl<F> Pattern for F
+where
+    F: Fn(char) -> bool + Sync + Send,
+{
+    fn is_match(&self, c: char) -> bool {
+        self(c)
+    }
+}
+
+// 从中点向后找到最近的字符边界，UTF-8 中一个字符最多 4 个字节，所以最多向后移动 3 次
+fn find_char_midpoint(chars: &str) -> usize {
+    let mut mid = chars.len() / 2;
+
+    while !chars.is_char_boundary(mid) {
+        mid += 1;
+    }
+
+    mid
+}
+
+pub struct Chars<'ch> {
+    chars: &'ch str,
+}
+
+impl<'ch> ParallelIterator for Chars<'ch> {
+    type Item = char;
+
+    #[instrument(skip_all)]
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("chars parallel iterator execute");
+
+        run_unindexed(CharsSplitable { chars: self.chars }, op)
+    }
+}
+
+struct CharsSplitable<'ch> {
+    chars: &'ch str,
+}
+
+impl<'ch> UnindexedSplitable for CharsSplitable<'ch> {
+    type Item = char;
+
+    fn split(self) -> (Self, Option<Self>) {
+        let index = find_char_midpoint(self.chars);
+
+        if index == 0 || index == self.chars.len() {
+            return (self, None);
+        }
+
+        let (left, right) = self.chars.split_at(index);
+
+        (
+            CharsSplitable { chars: left },
+            Some(CharsSplitable { chars: right }),
+        )
+    }
+
+    fn fold_with<C>(self, consumer: C) -> C
+    where
+        C: Consumer<Self::Item>,
+    {
+        consumer.consume_iter(self.chars.chars())
+    }
+}
+
+pub struct CharIndices<'ch> {
+    chars: &'ch str,
+}
+
+impl<'ch> ParallelIterator for CharIndices<'ch> {
+    type Item = (usize, char);
+
+    #[instrument(skip_all)]
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("char indices parallel iterator execute");
+
+        run_unindexed(
+            CharIndicesSplitable {
+                chars: self.chars,
+                offset: 0,
+            },
+            op,
+        )
+    }
+}
+
+// offset 记录当前片段在原字符串中的起始位置，保证返回的下标相对于原字符串
+struct CharIndicesSplitable<'ch> {
+    chars: &'ch str,
+    offset: usize,
+}
+
+impl<'ch> UnindexedSplitable for CharIndicesSplitable<'ch> {
+    type Item = (usize, char);
+
+    fn split(self) -> (Self, Option<Self>) {
+        let index = find_char_midpoint(self.chars);
+
+        if index == 0 || index == self.chars.len() {
+            return (self, None);
+        }
+
+        let (left, right) = self.chars.split_at(index);
+
+        (
+            CharIndicesSplitable {
+                chars: left,
+                offset: self.offset,
+            },
+            Some(CharIndicesSplitable {
+                chars: right,
+                offset: self.offset + index,
+            }),
+        )
+    }
+
+    fn fold_with<C>(self, consumer: C) -> C
+    where
+        C: Consumer<Self::Item>,
+    {
+        let offset = self.offset;
+
+        consumer.consume_iter(self.chars.char_indices().map(|(i, c)| (offset + i, c)))
+    }
+}
+
+// 按字节迭代不需要关心字符边界，长度已知
+pub struct Bytes<'ch> {
+    bytes: &'ch [u8],
+}
+
+impl<'ch> ParallelIterator for Bytes<'ch> {
+    type Item = u8;
+
+    #[instrument(skip_all)]
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("bytes parallel iterator execute");
+
+        run(BytesSplitable { bytes: self.bytes }, op)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'ch> IndexedParallelIterator for Bytes<'ch> {
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+struct BytesSplitable<'ch> {
+    bytes: &'ch [u8],
+}
+
+impl<'ch> Splitable for BytesSplitable<'ch> {
+    type Item = u8;
+
+    type IntoIter = Copied<slice::Iter<'ch, u8>>;
+
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let (left, right) = self.bytes.split_at(mid);
+
+        (
+            BytesSplitable { bytes: left },
+            BytesSplitable { bytes: right },
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.bytes.iter().copied()
+    }
+}
+
+pub struct Split<'ch, P> {
+    chars: &'ch str,
+    separator: P,
+}
+
+impl<'ch, P: Pattern> ParallelIterator for Split<'ch, P> {
+    type Item = &'ch str;
+
+    #[instrument(skip_all)]
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("split parallel iterator execute");
+
+        run_unindexed(
+            SplitSplitable::new(self.chars, &self.separator, false, false),
+            op,
+        )
+    }
+}
+
+pub struct SplitTerminator<'ch, P> {
+    chars: &'ch str,
+    terminator: P,
+}
+
+impl<'ch, P: Pattern> ParallelIterator for SplitTerminator<'ch, P> {
+    type Item = &'ch str;
+
+    #[instrument(skip_all)]
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("split terminator parallel iterator execute");
+
+        run_unindexed(
+            SplitSplitable::new(self.chars, &self.terminator, true, false),
+            op,
+        )
+    }
+}
+
+pub struct Lines<'ch> {
+    chars: &'ch str,
+}
+
+impl<'ch> ParallelIterator for Lines<'ch> {
+    type Item = &'ch str;
+
+    #[instrument(skip_all)]
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("lines parallel iterator execute");
+
+        // 和 str::lines 一样，只去掉以 "\n" 结尾的行末尾的 '\r'，最后一行没有 "\n" 时保留 '\r'
+        let chars = self.chars;
+        let start = chars.as_ptr() as usize;
+
+        chars
+            .par_split_terminator('\n')
+            .map(move |line| {
+                let end = line.as_ptr() as usize - start + line.len();
+
+                if end < chars.len() {
+                    line.strip_suffix('\r').unwrap_or(line)
+                } else {
+                    line
+                }
+            })
+            .execute(op)
+    }
+}
+
+pub struct SplitWhitespace<'ch> {
+    chars: &'ch str,
+}
+
+impl<'ch> ParallelIterator for SplitWhitespace<'ch> {
+    type Item = &'ch str;
+
+    #[instrument(skip_all)]
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("split whitespace parallel iterator execute");
+
+        run_unindexed(
+            SplitSplitable::new(self.chars, &char::is_whitespace, false, true),
+            op,
+        )
+    }
+}
+
+// 每个片段的两端要么是原字符串的两端，要么紧挨着一个分隔符，拆分时把中点附近的一个分隔符去掉，
+// 这样左右两个片段分别 split 的结果拼接起来，就等于整个片段 split 的结果
+// terminator 只对包含原字符串结尾的最右侧片段生效，skip_empty 会丢弃所有空串
+struct SplitSplitable<'ch, 'p, P> {
+    chars: &'ch str,
+    separator: &'p P,
+    terminator: bool,
+    skip_empty: bool,
+}
+
+impl<'ch, 'p, P: Pattern> SplitSplitable<'ch, 'p, P> {
+    fn new(chars: &'ch str, separator: &'p P, terminator: bool, skip_empty: bool) -> Self {
+        SplitSplitable {
+            chars,
+            separator,
+            terminator,
+            skip_empty,
+        }
+    }
+}
+
+impl<'ch, 'p, P: Pattern> UnindexedSplitable for SplitSplitable<'ch, 'p, P> {
+    type Item = &'ch str;
+
+    fn split(self) -> (Self, Option<Self>) {
+        let separator = self.separator;
+        let is_match = |c: char| separator.is_match(c);
+
+        // 优先在中点之后查找分隔符，找不到再向前查找
+        let mid = find_char_midpoint(self.chars);
+        let index = self.chars[mid..]
+            .find(is_match)
+            .map(|i| mid + i)
+            .or_else(|| self.chars[..mid].rfind(is_match));
+
+        let Some(index) = index else {
+            return (self, None);
+        };
+
+        let separator_len = self.chars[index..].chars().next().map_or(0, char::len_utf8);
+
+        let left = SplitSplitable {
+            chars: &self.chars[..index],
+            terminator: false,
+            ..self
+        };
+        let right = SplitSplitable {
+            chars: &self.chars[index + separator_len..],
+            ..self
+        };
+
+        (left, Some(right))
+    }
+
+    fn fold_with<C>(self, consumer: C) -> C
+    where
+        C: Consumer<Self::Item>,
+    {
+        let separator = self.separator;
+        let is_match = |c: char| separator.is_match(c);
+
+        if self.skip_empty {
+            consumer.consume_iter(self.chars.split(is_match).filter(|s| !s.is_empty()))
+        } else if self.terminator {
+            consumer.consume_iter(self.chars.split_terminator(is_match))
+        } else {
+            consumer.consume_iter(self.chars.split(is_match))
+        }
+    }
+}
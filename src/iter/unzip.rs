@@ -0,0 +1,334 @@
+use tracing::instrument;
+
+use super::vec::{Consumer, ParallelExtend, ParallelIterator, Reducer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+// 把一个 item 拆分给左右两个 consumer
+trait UnzipOp<T>: Sync + Send {
+    type Left: Send;
+    type Right: Send;
+
+    fn consume<FA, FB>(&self, item: T, left: FA, right: FB) -> (FA, FB)
+    where
+        FA: Consumer<Self::Left>,
+        FB: Consumer<Self::Right>;
+
+    // 左右两边是否都会收到每一个 item，只有这样两边才能沿用原迭代器的长度
+    fn indexable() -> bool {
+        false
+    }
+}
+
+struct Unzip;
+
+impl<A: Send, B: Send> UnzipOp<(A, B)> for Unzip {
+    type Left = A;
+    type Right = B;
+
+    fn consume<FA, FB>(&self, item: (A, B), left: FA, right: FB) -> (FA, FB)
+    where
+        FA: Consumer<A>,
+        FB: Consumer<B>,
+    {
+        (left.consume(item.0), right.consume(item.1))
+    }
+
+    fn indexable() -> bool {
+        true
+    }
+}
+
+struct Partition<P> {
+    predicate: P,
+}
+
+impl<P, T> UnzipOp<T> for Partition<P>
+where
+    P: Fn(&T) -> bool + Sync + Send,
+    T: Send,
+{
+    type Left = T;
+    type Right = T;
+
+    fn consume<FA, FB>(&self, item: T, left: FA, right: FB) -> (FA, FB)
+    where
+        FA: Consumer<T>,
+        FB: Consumer<T>,
+    {
+        if (self.predicate)(&item) {
+            (left.consume(item), right)
+        } else {
+            (left, right.consume(item))
+        }
+    }
+}
+
+struct PartitionMap<P> {
+    predicate: P,
+}
+
+impl<P, L, R, T> UnzipOp<T> for PartitionMap<P>
+where
+    P: Fn(T) -> Either<L, R> + Sync + Send,
+    L: Send,
+    R: Send,
+{
+    type Left = L;
+    type Right = R;
+
+    fn consume<FA, FB>(&self, item: T, left: FA, right: FB) -> (FA, FB)
+    where
+        FA: Consumer<L>,
+        FB: Consumer<R>,
+    {
+        match (self.predicate)(item) {
+            Either::Left(item) => (left.consume(item), right),
+            Either::Right(item) => (left, right.consume(item)),
+        }
+    }
+}
+
+#[instrument(skip_all)]
+pub(crate) fn unzip<I, A, B, FromA, FromB>(iter: I) -> (FromA, FromB)
+where
+    I: ParallelIterator<Item = (A, B)>,
+    FromA: Default + Send + ParallelExtend<A>,
+    FromB: Default + Send + ParallelExtend<B>,
+    A: Send,
+    B: Send,
+{
+    execute(iter, Unzip)
+}
+
+#[instrument(skip_all)]
+pub(crate) fn partition<I, A, B, P>(iter: I, predicate: P) -> (A, B)
+where
+    I: ParallelIterator,
+    A: Default + Send + ParallelExtend<I::Item>,
+    B: Default + Send + ParallelExtend<I::Item>,
+    P: Fn(&I::Item) -> bool + Sync + Send,
+{
+    execute(iter, Partition { predicate })
+}
+
+#[instrument(skip_all)]
+pub(crate) fn partition_map<I, A, B, P, L, R>(iter: I, predicate: P) -> (A, B)
+where
+    I: ParallelIterator,
+    A: Default + Send + ParallelExtend<L>,
+    B: Default + Send + ParallelExtend<R>,
+    P: Fn(I::Item) -> Either<L, R> + Sync + Send,
+    L: Send,
+    R: Send,
+{
+    execute(iter, PartitionMap { predicate })
+}
+
+// A 的 parallel_extend 执行 UnzipA 时拿到 A 的 consumer，再在其中调用 B 的 parallel_extend 拿到 B 的 consumer，
+// 最后由 UnzipB 把两个 consumer 组合起来只执行一次原迭代器，这样两边都沿用各自容器的 extend 逻辑，
+// 对于长度已知的迭代器 collect 到 Vec 时，两边都直接写入预留好的内存
+fn execute<I, OP, FromA, FromB>(iter: I, op: OP) -> (FromA, FromB)
+where
+    I: ParallelIterator,
+    OP: UnzipOp<I::Item>,
+    FromA: Default + Send + ParallelExtend<OP::Left>,
+    FromB: Default + Send + ParallelExtend<OP::Right>,
+{
+    let mut a = FromA::default();
+    let mut b = FromB::default();
+
+    a.parallel_extend(UnzipA {
+        iter,
+        op,
+        b: &mut b,
+    });
+
+    (a, b)
+}
+
+struct UnzipA<'b, I, OP, FromB> {
+    iter: I,
+    op: OP,
+    b: &'b mut FromB,
+}
+
+impl<'b, I, OP, FromB> ParallelIterator for UnzipA<'b, I, OP, FromB>
+where
+    I: ParallelIterator,
+    OP: UnzipOp<I::Item>,
+    FromB: Default + Send + ParallelExtend<OP::Right>,
+{
+    type Item = OP::Left;
+
+    fn execute<C>(self, consumer: C) -> C::Output
+    where
+        C: Consumer<Self::Item>,
+    {
+        let mut result = None;
+
+        self.b.parallel_extend(UnzipB {
+            iter: self.iter,
+            op: self.op,
+            left_consumer: consumer,
+            left_result: &mut result,
+        });
+
+        result.expect("unzip consumers didn't execute")
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        if OP::indexable() {
+            self.iter.opt_len()
+        } else {
+            None
+        }
+    }
+}
+
+struct UnzipB<'r, I, OP, CA>
+where
+    I: ParallelIterator,
+    OP: UnzipOp<I::Item>,
+    CA: Consumer<OP::Left>,
+{
+    iter: I,
+    op: OP,
+    left_consumer: CA,
+    left_result: &'r mut Option<CA::Output>,
+}
+
+impl<'r, I, OP, CA> ParallelIterator for UnzipB<'r, I, OP, CA>
+where
+    I: ParallelIterator,
+    OP: UnzipOp<I::Item>,
+    CA: Consumer<OP::Left>,
+{
+    type Item = OP::Right;
+
+    fn execute<C>(self, consumer: C) -> C::Output
+    where
+        C: Consumer<Self::Item>,
+    {
+        let (left, right) = self.iter.execute(UnzipConsumer {
+            op: &self.op,
+            left: self.left_consumer,
+            right: consumer,
+        });
+
+        *self.left_result = Some(left);
+
+        right
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        if OP::indexable() {
+            self.iter.opt_len()
+        } else {
+            None
+        }
+    }
+}
+
+struct UnzipConsumer<'o, OP, CA, CB> {
+    op: &'o OP,
+    left: CA,
+    right: CB,
+}
+
+impl<'o, T, OP, CA, CB> Consumer<T> for UnzipConsumer<'o, OP, CA, CB>
+where
+    T: Send,
+    OP: UnzipOp<T>,
+    CA: Consumer<OP::Left>,
+    CB: Consumer<OP::Right>,
+{
+    type Output = (CA::Output, CB::Output);
+    type Reducer = UnzipReducer<CA::Reducer, CB::Reducer>;
+
+    fn consume(self, item: T) -> Self {
+        let (left, right) = self.op.consume(item, self.left, self.right);
+
+        UnzipConsumer {
+            op: self.op,
+            left,
+            right,
+        }
+    }
+
+    fn consume_iter<I>(mut self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        for item in iter {
+            self = self.consume(item);
+
+            if self.full() {
+                break;
+            }
+        }
+
+        self
+    }
+
+    fn complete(self) -> Self::Output {
+        (self.left.complete(), self.right.complete())
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left1, left2, left_reducer) = self.left.split_at(index);
+        let (right1, right2, right_reducer) = self.right.split_at(index);
+
+        (
+            UnzipConsumer {
+                op: self.op,
+                left: left1,
+                right: right1,
+            },
+            UnzipConsumer {
+                op: self.op,
+                left: left2,
+                right: right2,
+            },
+            UnzipReducer {
+                left: left_reducer,
+                right: right_reducer,
+            },
+        )
+    }
+
+    fn min_len(&self) -> usize {
+        self.left.min_len().max(self.right.min_len())
+    }
+
+    fn max_len(&self) -> usize {
+        self.left.max_len().min(self.right.max_len())
+    }
+
+    // 只有两边都不再需要 item 时才能停止
+    fn full(&self) -> bool {
+        self.left.full() && self.right.full()
+    }
+}
+
+struct UnzipReducer<RA, RB> {
+    left: RA,
+    right: RB,
+}
+
+impl<A, B, RA, RB> Reducer<(A, B)> for UnzipReducer<RA, RB>
+where
+    RA: Reducer<A>,
+    RB: Reducer<B>,
+{
+    fn reduce(self, left: (A, B), right: (A, B)) -> (A, B) {
+        (
+            self.left.reduce(left.0, right.0),
+            self.right.reduce(left.1, right.1),
+        )
+    }
+}
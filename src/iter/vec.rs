@@ -5,11 +5,25 @@
 // 3. 将数据结构转换可分割的结构（vec -> len split, string -> mid split), 适合分割的数据结构需要从数据结构本身获取
 
 use core::slice;
-use std::ptr;
+use std::{
+    mem,
+    ops::{Bound, Range, RangeBounds},
+    ptr,
+};
 
 use tracing::{instrument, trace};
 
-use crate::{join, thread_data::CORE_NUMS};
+use super::{
+    extend::extend_unindexed,
+    fallible::{try_reduce, Try, TryFold, WhileSome},
+    map_with::{MapInit, MapWith},
+    scan::par_scan,
+    unzip::{partition, partition_map, unzip, Either},
+};
+use crate::{
+    cancel::{self, CancelConsumer, CancellationToken, WithCancel},
+    current_num_threads, join, ThreadWoker,
+};
 
 impl<T: Send> IntoParallelIterator for Vec<T> {
     type Item = T;
@@ -32,6 +46,47 @@ pub trait IntoParallelIterator {
     fn into_par_iter(self) -> Self::Iter;
 }
 
+// 借用版本的入口函数，`par_iter` / `par_iter_mut` 只是 `&T` / `&mut T` 上 `into_par_iter` 的简写
+pub trait IntoParallelRefIterator<'data> {
+    type Item: Send + 'data;
+    type Iter: ParallelIterator<Item = Self::Item>;
+
+    fn par_iter(&'data self) -> Self::Iter;
+}
+
+impl<'data, I> IntoParallelRefIterator<'data> for I
+where
+    I: 'data + ?Sized,
+    &'data I: IntoParallelIterator,
+{
+    type Item = <&'data I as IntoParallelIterator>::Item;
+    type Iter = <&'data I as IntoParallelIterator>::Iter;
+
+    fn par_iter(&'data self) -> Self::Iter {
+        self.into_par_iter()
+    }
+}
+
+pub trait IntoParallelRefMutIterator<'data> {
+    type Item: Send + 'data;
+    type Iter: ParallelIterator<Item = Self::Item>;
+
+    fn par_iter_mut(&'data mut self) -> Self::Iter;
+}
+
+impl<'data, I> IntoParallelRefMutIterator<'data> for I
+where
+    I: 'data + ?Sized,
+    &'data mut I: IntoParallelIterator,
+{
+    type Item = <&'data mut I as IntoParallelIterator>::Item;
+    type Iter = <&'data mut I as IntoParallelIterator>::Iter;
+
+    fn par_iter_mut(&'data mut self) -> Self::Iter {
+        self.into_par_iter()
+    }
+}
+
 impl<T: Send> ParallelIterator for IntoIter<T> {
     type Item = T;
 
@@ -43,21 +98,121 @@ impl<T: Send> ParallelIterator for IntoIter<T> {
         // 1. self => splitable data strcture
         // 2. splitable data structure => producer
 
-        let len = self.vec.len();
-        let splitable = VecSplitable {
-            vec: &mut self.vec,
-            len,
-        };
+        // 所有元素的所有权都交给 drain，执行结束后 vec 的长度为 0，只负责释放内存
+        self.vec.par_drain(..).execute(op)
+    }
 
-        run(splitable, op)
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
     }
+}
 
+impl<T: Send> IndexedParallelIterator for IntoIter<T> {
     fn len(&self) -> usize {
         self.vec.len()
     }
 }
 
-trait Splitable: Sized + Send {
+pub trait ParallelDrainRange<Idx = usize> {
+    type Item: Send;
+    type Iter: ParallelIterator<Item = Self::Item>;
+
+    fn par_drain<R: RangeBounds<Idx>>(self, range: R) -> Self::Iter;
+}
+
+impl<'data, T: Send> ParallelDrainRange<usize> for &'data mut Vec<T> {
+    type Item = T;
+    type Iter = Drain<'data, T>;
+
+    #[instrument(skip_all)]
+    fn par_drain<R: RangeBounds<usize>>(self, range: R) -> Drain<'data, T> {
+        let orig_len = self.len();
+
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1).expect("range start overflow"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1).expect("range end overflow"),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => orig_len,
+        };
+
+        assert!(start <= end, "drain range start {start} > end {end}");
+        assert!(end <= orig_len, "drain range end {end} > len {orig_len}");
+
+        Drain {
+            vec: self,
+            range: start..end,
+            orig_len,
+        }
+    }
+}
+
+pub struct Drain<'data, T: Send> {
+    vec: &'data mut Vec<T>,
+    range: Range<usize>,
+    orig_len: usize,
+}
+
+impl<'data, T: Send> ParallelIterator for Drain<'data, T> {
+    type Item = T;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("vec drain parallel iterator execute");
+
+        let Range { start, end } = self.range;
+
+        unsafe {
+            // 先把长度截断到 start，range 内的元素从这里开始只由 VecSplitable 负责 drop，
+            // 即便中途 panic 也不会被 vec 再 drop 一次
+            self.vec.set_len(start);
+
+            let vec = slice::from_raw_parts_mut(self.vec.as_mut_ptr().add(start), end - start);
+
+            run(VecSplitable { vec }, op)
+        }
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'data, T: Send> IndexedParallelIterator for Drain<'data, T> {
+    fn len(&self) -> usize {
+        self.range.len()
+    }
+}
+
+impl<'data, T: Send> Drop for Drain<'data, T> {
+    fn drop(&mut self) {
+        let Range { start, end } = self.range;
+
+        if self.vec.len() == self.orig_len {
+            // 没有被执行过，元素还归 vec 所有，直接用普通的 drain 移除
+            self.vec.drain(start..end);
+        } else if start == end {
+            unsafe { self.vec.set_len(self.orig_len) };
+        } else if end < self.orig_len {
+            // range 内的元素已经被消费或 drop，把尾部的元素移动到 start 处
+            unsafe {
+                let ptr = self.vec.as_mut_ptr().add(start);
+                let tail_ptr = self.vec.as_ptr().add(end);
+                let tail_len = self.orig_len - end;
+
+                ptr::copy(tail_ptr, ptr, tail_len);
+                self.vec.set_len(start + tail_len);
+            }
+        }
+    }
+}
+
+pub(crate) trait Splitable: Sized + Send {
     type Item: Send;
     type IntoIter: Iterator<Item = Self::Item>;
 
@@ -85,12 +240,21 @@ where
     type Item = T;
 }
 
+// 提前结束（panic 或者 consumer 不再消费）时，没有被读出的元素需要在这里 drop
+impl<'data, T> Drop for SliceSplitable<'data, T> {
+    fn drop(&mut self) {
+        let rest: &mut [T] = mem::take(&mut self.slice).into_slice();
+
+        unsafe { ptr::drop_in_place(rest) };
+    }
+}
+
+// 拥有 slice 内元素的所有权，未被转换成迭代器的部分（例如拆分后还没有执行的一半）在 drop 时释放
 struct VecSplitable<'data, T>
 where
     T: Send,
 {
     vec: &'data mut [T],
-    len: usize,
 }
 
 impl<'data, T> Splitable for VecSplitable<'data, T>
@@ -101,19 +265,10 @@ where
 
     type IntoIter = SliceSplitable<'data, T>;
 
-    fn split_at(self, mid: usize) -> (Self, Self) {
-        let (left, right) = self.vec.split_at_mut(mid);
+    fn split_at(mut self, mid: usize) -> (Self, Self) {
+        let (left, right) = mem::take(&mut self.vec).split_at_mut(mid);
 
-        (
-            VecSplitable {
-                vec: left,
-                len: mid,
-            },
-            VecSplitable {
-                vec: right,
-                len: self.len - mid,
-            },
-        )
+        (VecSplitable { vec: left }, VecSplitable { vec: right })
     }
 
     fn len(&self) -> usize {
@@ -121,7 +276,7 @@ where
     }
 
     fn into_iter(mut self) -> Self::IntoIter {
-        let slice = std::mem::take(&mut self.vec);
+        let slice = mem::take(&mut self.vec);
 
         SliceSplitable {
             slice: slice.iter_mut(),
@@ -129,8 +284,18 @@ where
     }
 }
 
+impl<'data, T> Drop for VecSplitable<'data, T>
+where
+    T: Send,
+{
+    fn drop(&mut self) {
+        let rest: &mut [T] = mem::take(&mut self.vec);
+
+        unsafe { ptr::drop_in_place(rest) };
+    }
+}
+
 // tools function trait
-#[allow(clippy::len_without_is_empty)]
 pub trait ParallelIterator: Sized {
     type Item: Send;
 
@@ -143,13 +308,159 @@ pub trait ParallelIterator: Sized {
     }
 
     #[instrument(skip_all)]
-    fn map<OP>(self, op: OP) -> Map<Self, OP>
+    fn map<OP, R>(self, op: OP) -> Map<Self, OP>
     where
-        OP: Fn(Self::Item) -> Self::Item + Send + Sync,
+        OP: Fn(Self::Item) -> R + Send + Sync,
+        R: Send,
     {
         Map { iter: self, op }
     }
 
+    // init 在每次拆分时 clone 一份，同一个叶子中的 item 共享同一份可变状态
+    fn map_with<OP, T, R>(self, init: T, op: OP) -> MapWith<Self, T, OP>
+    where
+        OP: Fn(&mut T, Self::Item) -> R + Send + Sync,
+        T: Send + Clone,
+        R: Send,
+    {
+        MapWith::new(self, init, op)
+    }
+
+    // 每个叶子调用一次 init 创建状态
+    fn map_init<OP, INIT, T, R>(self, init: INIT, op: OP) -> MapInit<Self, INIT, OP>
+    where
+        OP: Fn(&mut T, Self::Item) -> R + Send + Sync,
+        INIT: Fn() -> T + Send + Sync,
+        R: Send,
+    {
+        MapInit::new(self, init, op)
+    }
+
+    #[instrument(skip_all)]
+    fn for_each_with<OP, T>(self, init: T, op: OP)
+    where
+        OP: Fn(&mut T, Self::Item) + Send + Sync,
+        T: Send + Clone,
+    {
+        self.map_with(init, op).collect()
+    }
+
+    #[instrument(skip_all)]
+    fn for_each_init<OP, INIT, T>(self, init: INIT, op: OP)
+    where
+        OP: Fn(&mut T, Self::Item) + Send + Sync,
+        INIT: Fn() -> T + Send + Sync,
+    {
+        self.map_init(init, op).collect()
+    }
+
+    // 拆分后每个叶子至少包含 min 个 item
+    // 只对按长度拆分的迭代器生效，par_bridge / par_chars 等长度未知的迭代器会忽略这个限制
+    fn with_min_len(self, min: usize) -> MinLen<Self> {
+        MinLen { iter: self, min }
+    }
+
+    // 拆分后每个叶子最多包含 max 个 item，和 with_min_len 冲突时以 min 为准
+    // 只对按长度拆分的迭代器生效，par_bridge / par_chars 等长度未知的迭代器会忽略这个限制
+    fn with_max_len(self, max: usize) -> MaxLen<Self> {
+        MaxLen { iter: self, max }
+    }
+
+    // 遇到第一个 None 时停止
+    fn while_some<T>(self) -> WhileSome<Self>
+    where
+        Self: ParallelIterator<Item = Option<T>>,
+        T: Send,
+    {
+        WhileSome::new(self)
+    }
+
+    #[instrument(skip_all)]
+    fn try_for_each<OP, R>(self, op: OP) -> R
+    where
+        OP: Fn(Self::Item) -> R + Send + Sync,
+        R: Try<Output = ()> + Send,
+    {
+        try_reduce(self.map(op), || (), |(), ()| R::from_output(()))
+    }
+
+    #[instrument(skip_all)]
+    fn try_for_each_with<OP, T, R>(self, init: T, op: OP) -> R
+    where
+        OP: Fn(&mut T, Self::Item) -> R + Send + Sync,
+        T: Send + Clone,
+        R: Try<Output = ()> + Send,
+    {
+        try_reduce(self.map_with(init, op), || (), |(), ()| R::from_output(()))
+    }
+
+    #[instrument(skip_all)]
+    fn try_for_each_init<OP, INIT, T, R>(self, init: INIT, op: OP) -> R
+    where
+        OP: Fn(&mut T, Self::Item) -> R + Send + Sync,
+        INIT: Fn() -> T + Send + Sync,
+        R: Try<Output = ()> + Send,
+    {
+        try_reduce(self.map_init(init, op), || (), |(), ()| R::from_output(()))
+    }
+
+    fn try_fold<T, R, ID, F>(self, identity: ID, fold_op: F) -> TryFold<Self, ID, F>
+    where
+        ID: Fn() -> T + Send + Sync,
+        F: Fn(T, Self::Item) -> R + Send + Sync,
+        R: Try<Output = T> + Send,
+    {
+        TryFold::new(self, identity, fold_op)
+    }
+
+    #[instrument(skip_all)]
+    fn try_reduce<T, ID, OP>(self, identity: ID, op: OP) -> Self::Item
+    where
+        ID: Fn() -> T + Send + Sync,
+        OP: Fn(T, T) -> Self::Item + Send + Sync,
+        Self::Item: Try<Output = T>,
+    {
+        try_reduce(self, identity, op)
+    }
+
+    // 把 (A, B) 拆分到两个容器中，长度已知时 collect 到 Vec 的两边都直接写入预留好的内存
+    fn unzip<A, B, FromA, FromB>(self) -> (FromA, FromB)
+    where
+        Self: ParallelIterator<Item = (A, B)>,
+        FromA: Default + Send + ParallelExtend<A>,
+        FromB: Default + Send + ParallelExtend<B>,
+        A: Send,
+        B: Send,
+    {
+        unzip(self)
+    }
+
+    // predicate 返回 true 的 item 放入第一个容器，其余放入第二个容器
+    fn partition<A, B, P>(self, predicate: P) -> (A, B)
+    where
+        A: Default + Send + ParallelExtend<Self::Item>,
+        B: Default + Send + ParallelExtend<Self::Item>,
+        P: Fn(&Self::Item) -> bool + Send + Sync,
+    {
+        partition(self, predicate)
+    }
+
+    fn partition_map<A, B, P, L, R>(self, predicate: P) -> (A, B)
+    where
+        A: Default + Send + ParallelExtend<L>,
+        B: Default + Send + ParallelExtend<R>,
+        P: Fn(Self::Item) -> Either<L, R> + Send + Sync,
+        L: Send,
+        R: Send,
+    {
+        partition_map(self, predicate)
+    }
+
+    // 在 token 的作用域中执行之后的 for_each / collect，token 被取消时返回 Err(Cancelled)
+    fn with_cancel(self, token: &CancellationToken) -> WithCancel<'_, Self> {
+        WithCancel::new(self, token)
+    }
+
     fn execute<OP>(self, op: OP) -> OP::Output
     where
         OP: Consumer<Self::Item>;
@@ -161,7 +472,25 @@ pub trait ParallelIterator: Sized {
         C::from_par_iter(self)
     }
 
+    // 只有能够预先知道长度的迭代器才返回 Some，collect 到 Vec 时据此决定能否直接写入预留好的内存
+    fn opt_len(&self) -> Option<usize> {
+        None
+    }
+}
+
+// 长度已知、并且 consumer.split_at(index) 的 index 与 item 的位置一一对应的迭代器
+#[allow(clippy::len_without_is_empty)]
+pub trait IndexedParallelIterator: ParallelIterator {
     fn len(&self) -> usize;
+
+    // 并行的 inclusive 前缀和，op 必须满足结合律，identity 是 op 的单位元
+    fn par_scan<ID, OP>(self, identity: ID, op: OP) -> Vec<Self::Item>
+    where
+        ID: Fn() -> Self::Item + Send + Sync,
+        OP: Fn(&Self::Item, &Self::Item) -> Self::Item + Send + Sync,
+    {
+        par_scan(self, identity, op)
+    }
 }
 
 impl<T: ParallelIterator> IntoParallelIterator for T {
@@ -184,21 +513,214 @@ impl<T: Send> FromParallelIterator<T> for Vec<T> {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct SendPtr<T>(*mut T);
+pub struct MinLen<I> {
+    iter: I,
+    min: usize,
+}
+
+impl<I: ParallelIterator> ParallelIterator for MinLen<I> {
+    type Item = I::Item;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        self.iter.execute(MinLenConsumer {
+            base: op,
+            min: self.min,
+        })
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.iter.opt_len()
+    }
+}
+
+impl<I: IndexedParallelIterator> IndexedParallelIterator for MinLen<I> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+struct MinLenConsumer<C> {
+    base: C,
+    min: usize,
+}
+
+impl<T, C> Consumer<T> for MinLenConsumer<C>
+where
+    T: Send,
+    C: Consumer<T>,
+{
+    type Output = C::Output;
+    type Reducer = C::Reducer;
+
+    fn consume(self, item: T) -> Self {
+        MinLenConsumer {
+            base: self.base.consume(item),
+            min: self.min,
+        }
+    }
+
+    fn consume_iter<I>(self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        MinLenConsumer {
+            base: self.base.consume_iter(iter),
+            min: self.min,
+        }
+    }
+
+    fn complete(self) -> Self::Output {
+        self.base.complete()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left, right, reducer) = self.base.split_at(index);
+
+        (
+            MinLenConsumer {
+                base: left,
+                min: self.min,
+            },
+            MinLenConsumer {
+                base: right,
+                min: self.min,
+            },
+            reducer,
+        )
+    }
+
+    fn min_len(&self) -> usize {
+        self.min.max(self.base.min_len())
+    }
+
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+pub struct MaxLen<I> {
+    iter: I,
+    max: usize,
+}
+
+impl<I: ParallelIterator> ParallelIterator for MaxLen<I> {
+    type Item = I::Item;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        self.iter.execute(MaxLenConsumer {
+            base: op,
+            max: self.max,
+        })
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.iter.opt_len()
+    }
+}
+
+impl<I: IndexedParallelIterator> IndexedParallelIterator for MaxLen<I> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+struct MaxLenConsumer<C> {
+    base: C,
+    max: usize,
+}
+
+impl<T, C> Consumer<T> for MaxLenConsumer<C>
+where
+    T: Send,
+    C: Consumer<T>,
+{
+    type Output = C::Output;
+    type Reducer = C::Reducer;
+
+    fn consume(self, item: T) -> Self {
+        MaxLenConsumer {
+            base: self.base.consume(item),
+            max: self.max,
+        }
+    }
+
+    fn consume_iter<I>(self, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        MaxLenConsumer {
+            base: self.base.consume_iter(iter),
+            max: self.max,
+        }
+    }
+
+    fn complete(self) -> Self::Output {
+        self.base.complete()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left, right, reducer) = self.base.split_at(index);
+
+        (
+            MaxLenConsumer {
+                base: left,
+                max: self.max,
+            },
+            MaxLenConsumer {
+                base: right,
+                max: self.max,
+            },
+            reducer,
+        )
+    }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+
+    fn max_len(&self) -> usize {
+        self.max.min(self.base.max_len())
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct SendPtr<T>(pub(crate) *mut T);
 
 unsafe impl<T> Send for SendPtr<T> {}
 
+impl<T> Clone for SendPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SendPtr<T> {}
+
 impl<T> SendPtr<T> {
-    fn new(ptr: *mut T) -> Self {
+    pub(crate) fn new(ptr: *mut T) -> Self {
         Self(ptr)
     }
 
-    fn add(&self, index: usize) -> Self {
-        Self((self.0 as usize + index * std::mem::size_of::<T>()) as *mut T)
+    pub(crate) fn add(&self, index: usize) -> Self {
+        Self(unsafe { self.0.add(index) })
     }
 }
 
+// 直接把 item 写入 vec 预留好的内存中，每个 consumer 只负责 [start, start + len) 这一段
 struct CollectConsumer<T>
 where
     T: Send,
@@ -214,17 +736,55 @@ where
 {
     fn new(vec: &mut Vec<T>, len: usize) -> Self {
         let start = vec.len();
+        assert!(vec.capacity() - start >= len);
+
         CollectConsumer {
             start: SendPtr::new(unsafe { vec.as_mut_ptr().add(start) }),
             len,
             index: 0,
         }
     }
+
+    fn write(&mut self, item: T) {
+        assert!(self.index < self.len, "too many values pushed to consumer");
+
+        unsafe {
+            self.start.0.add(self.index).write(item);
+        }
+        self.index += 1;
+    }
 }
 
+// consume 过程中 panic 时 consumer 会在 unwind 中被 drop，这里释放已经写入的元素
+impl<T> Drop for CollectConsumer<T>
+where
+    T: Send,
+{
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.start.0, self.index));
+        }
+    }
+}
+
+// 记录一段已经初始化的连续内存，在被 reduce 合并或者被 forget 之前，drop 时负责释放这些元素
 struct CollectResult<T> {
     start: SendPtr<T>,
-    len: usize,
+    total_len: usize,
+    initialized_len: usize,
+}
+
+unsafe impl<T: Send> Send for CollectResult<T> {}
+
+impl<T> Drop for CollectResult<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
+                self.start.0,
+                self.initialized_len,
+            ));
+        }
+    }
 }
 
 struct CollectReducer {}
@@ -236,11 +796,15 @@ pub trait Reducer<T> {
 impl CollectReducer {}
 
 impl<T> Reducer<CollectResult<T>> for CollectReducer {
-    fn reduce(self, left: CollectResult<T>, right: CollectResult<T>) -> CollectResult<T> {
-        CollectResult {
-            start: left.start,
-            len: left.len + right.len,
+    fn reduce(self, mut left: CollectResult<T>, mut right: CollectResult<T>) -> CollectResult<T> {
+        // 只有左边完整写入、右边紧挨着左边时才能合并，否则右边的元素随 right 一起 drop，
+        // 最终的 initialized_len 会小于 len，在 extend 中被检查出来
+        if left.start.add(left.initialized_len).0 == right.start.0 {
+            left.total_len += right.total_len;
+            left.initialized_len += mem::replace(&mut right.initialized_len, 0);
         }
+
+        left
     }
 }
 
@@ -252,10 +816,7 @@ where
     type Reducer = CollectReducer;
 
     fn consume(mut self, item: T) -> Self {
-        unsafe {
-            self.start.0.add(self.index).write(item);
-            self.index += 1;
-        }
+        self.write(item);
 
         self
     }
@@ -265,24 +826,29 @@ where
         I: IntoIterator<Item = T>,
     {
         for item in iter {
-            unsafe {
-                self.start.0.add(self.index).write(item);
-                self.index += 1;
-            }
+            self.write(item);
         }
 
         self
     }
 
     fn complete(self) -> Self::Output {
-        CollectResult {
+        let result = CollectResult {
             start: self.start,
-            len: self.len,
-        }
+            total_len: self.len,
+            initialized_len: self.index,
+        };
+
+        // 所有权转移给 result
+        mem::forget(self);
+
+        result
     }
 
     fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
         let len = self.len;
+        assert!(index <= len);
+        assert_eq!(self.index, 0, "split a consumer after consuming");
 
         let right = CollectConsumer {
             start: self.start.add(index),
@@ -306,11 +872,35 @@ where
 {
     let iter = i.into_par_iter();
 
-    let len = iter.len();
+    match iter.opt_len() {
+        Some(len) => collect_with_consumer(iter, len, vec),
+        // 长度未知时无法预留出每个 item 的位置，退化成先收集到各个叶子的局部 Vec 中再合并
+        None => extend_unindexed(iter, vec),
+    }
+}
 
+fn collect_with_consumer<I>(iter: I, len: usize, vec: &mut Vec<I::Item>)
+where
+    I: ParallelIterator,
+{
     vec.reserve(len);
 
-    iter.execute(CollectConsumer::new(vec, len));
+    let result = iter.execute(CollectConsumer::new(vec, len));
+
+    let actual = result.initialized_len;
+    // 被取消时写入的元素可能不足 len 个，丢弃已经写入的元素，vec 保持不变，
+    // 由外层的 install_with_token 返回 Err(Cancelled)
+    if actual != len && cancel::is_cancelled() {
+        drop(result);
+        return;
+    }
+    assert!(
+        actual == len,
+        "expected {len} total writes, but got {actual}"
+    );
+
+    // 元素的所有权交还给 vec
+    mem::forget(result);
 
     let new_len = len + vec.len();
 
@@ -319,23 +909,28 @@ where
     };
 }
 
-trait ParallelExtend<I: IntoParallelIterator> {
-    fn parallel_extend(&mut self, i: I);
-}
-
-impl<I> ParallelExtend<I> for Vec<I::Item>
+pub trait ParallelExtend<T>
 where
-    I: IntoParallelIterator,
+    T: Send,
 {
-    fn parallel_extend(&mut self, i: I) {
-        extend(i, self);
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = T>;
+}
+
+impl<T: Send> ParallelExtend<T> for Vec<T> {
+    fn parallel_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        extend(par_iter, self);
     }
 }
 
-fn collect<C, I>(i: I) -> C
+pub(crate) fn collect<C, I>(i: I) -> C
 where
     I: IntoParallelIterator,
-    C: ParallelExtend<I> + Default,
+    C: ParallelExtend<I::Item> + Default,
 {
     let mut v = C::default();
 
@@ -381,6 +976,20 @@ where
     fn complete(self) -> Self::Output;
 
     fn split_at(self, index: usize) -> (Self, Self, Self::Reducer);
+
+    // 拆分时的长度限制，由 with_min_len / with_max_len 设置，包装其它 consumer 的 consumer 需要转发给内部的 consumer
+    fn min_len(&self) -> usize {
+        1
+    }
+
+    fn max_len(&self) -> usize {
+        usize::MAX
+    }
+
+    // 返回 true 表示不再需要更多的 item（例如 try_* 已经遇到错误），run 会跳过还没有开始的拆分
+    fn full(&self) -> bool {
+        false
+    }
 }
 
 // map 准确来说并不是一个 consumer，它会消耗之前的数据并产生一个新的数据，并且后续由其它 consumer 消费 (for_each, collect)
@@ -399,12 +1008,13 @@ pub struct Map<I, F> {
 
 // impl <T>Consumer<T> for  Map<> {}
 
-impl<I, F> ParallelIterator for Map<I, F>
+impl<I, F, R> ParallelIterator for Map<I, F>
 where
     I: ParallelIterator,
-    F: Fn(I::Item) -> I::Item + Sync,
+    F: Fn(I::Item) -> R + Sync,
+    R: Send,
 {
-    type Item = I::Item;
+    type Item = R;
 
     fn execute<OP>(self, op: OP) -> OP::Output
     where
@@ -418,6 +1028,17 @@ where
         self.iter.execute(consumer)
     }
 
+    fn opt_len(&self) -> Option<usize> {
+        self.iter.opt_len()
+    }
+}
+
+impl<I, F, R> IndexedParallelIterator for Map<I, F>
+where
+    I: IndexedParallelIterator,
+    F: Fn(I::Item) -> R + Sync,
+    R: Send,
+{
     fn len(&self) -> usize {
         self.iter.len()
     }
@@ -476,6 +1097,18 @@ where
             reducer,
         )
     }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
 }
 
 struct ForEachConsumer<'f, F> {
@@ -522,19 +1155,52 @@ impl<'f, F> ForEachConsumer<'f, F> {
     }
 }
 
+// 自适应拆分：初始最多拆成 current_num_threads 份，如果某一半被其它 worker 窃取，说明有空闲的线程，
+// 此时重新把拆分次数补回到线程数，继续拆分以便负载均衡
 #[derive(Debug, Clone, Copy)]
 struct Spliter {
-    len: usize,
+    splits: usize,
+    threads: usize,
+    min: usize,
+    max: usize,
 }
 
 impl Spliter {
-    fn new() -> Self {
-        Self { len: CORE_NUMS }
+    fn new(len: usize, min: usize, max: usize) -> Self {
+        let threads = current_num_threads();
+        // max_len 限制了每个叶子的最大长度，所以至少需要 len / max 次拆分
+        let splits = threads.max(len / max.max(1));
+
+        Self {
+            splits,
+            threads,
+            min: min.max(1),
+            max: max.max(1),
+        }
+    }
+
+    // min 和 max 冲突时以 min 为准
+    fn try_split(&mut self, len: usize, stolen: bool) -> bool {
+        if len / 2 < self.min {
+            return false;
+        }
+
+        // 超过 max 的部分不管拆分次数是否用完都继续拆分
+        if len > self.max {
+            self.splits /= 2;
+            return true;
+        }
+
+        self.try_split_unindexed(stolen)
     }
 
-    fn try_split(&mut self) -> bool {
-        if self.len / 2 != 0 {
-            self.len /= 2;
+    // 不知道长度时只根据拆分次数和是否被窃取来决定
+    fn try_split_unindexed(&mut self, stolen: bool) -> bool {
+        if stolen {
+            self.splits = self.threads.max(self.splits / 2);
+            true
+        } else if self.splits > 0 {
+            self.splits /= 2;
             true
         } else {
             false
@@ -542,38 +1208,111 @@ impl Spliter {
     }
 }
 
-fn run<T, P, C>(producer: P, consumer: C) -> C::Output
+pub(crate) fn run<T, P, C>(producer: P, consumer: C) -> C::Output
 where
     T: Send,
     P: Splitable<Item = T>,
     C: Consumer<T>,
 {
-    let spliter = Spliter::new();
     let len = producer.len();
+    let spliter = Spliter::new(len, consumer.min_len(), consumer.max_len());
+    let consumer = CancelConsumer::new(consumer);
 
     fn helper<T: Send, P: Splitable<Item = T>, C: Consumer<T>>(
         mut spliter: Spliter,
-        len: usize,
+        stolen: bool,
         producer: P,
         consumer: C,
     ) -> C::Output {
-        if spliter.try_split() {
+        if consumer.full() {
+            trace!("consumer full, skip {} items", producer.len());
+            return consumer.complete();
+        }
+
+        // 最小的分割单位是 producer 的一个 item（对于 chunks 来说是一个 chunk），不再继续拆分单个 item
+        let len = producer.len();
+        if len > 1 && spliter.try_split(len, stolen) {
             trace!("spliter split");
-            let mid = producer.len() / 2;
+            let mid = len / 2;
             let (left, right) = producer.split_at(mid);
             let (left_consumer, right_consumer, reducer) = consumer.split_at(mid);
 
+            let origin = ThreadWoker::try_current_index();
             let (l, r) = join(
-                || helper(spliter, mid, left, left_consumer),
-                || helper(spliter, len - mid, right, right_consumer),
+                || helper(spliter, false, left, left_consumer),
+                || {
+                    let stolen = ThreadWoker::try_current_index() != origin;
+                    helper(spliter, stolen, right, right_consumer)
+                },
             );
 
             reducer.reduce(l, r)
         } else {
-            trace!("consumer len: {}", producer.len());
+            trace!("consumer len: {}", len);
             consumer.consume_iter(producer.into_iter()).complete()
         }
     }
 
-    helper(spliter, len, producer, consumer)
+    helper(spliter, false, producer, consumer)
+}
+
+// 长度未知、只能大致从中间拆分的 producer，例如需要落在字符边界上的字符串
+pub(crate) trait UnindexedSplitable: Sized + Send {
+    type Item: Send;
+
+    // 无法继续拆分时返回 None
+    fn split(self) -> (Self, Option<Self>);
+
+    fn fold_with<C>(self, consumer: C) -> C
+    where
+        C: Consumer<Self::Item>;
+}
+
+pub(crate) fn run_unindexed<T, P, C>(producer: P, consumer: C) -> C::Output
+where
+    T: Send,
+    P: UnindexedSplitable<Item = T>,
+    C: Consumer<T>,
+{
+    // 长度未知，with_min_len / with_max_len 的限制不生效，只按拆分次数拆分
+    let spliter = Spliter::new(0, 1, usize::MAX);
+    let consumer = CancelConsumer::new(consumer);
+
+    fn helper<T: Send, P: UnindexedSplitable<Item = T>, C: Consumer<T>>(
+        mut spliter: Spliter,
+        stolen: bool,
+        producer: P,
+        consumer: C,
+    ) -> C::Output {
+        if consumer.full() {
+            trace!("consumer full, skip unindexed producer");
+            return consumer.complete();
+        }
+
+        if spliter.try_split_unindexed(stolen) {
+            match producer.split() {
+                (left, Some(right)) => {
+                    trace!("unindexed spliter split");
+                    // 拆分的位置未知，只有不关心位置的 consumer 才会走到这里
+                    let (left_consumer, right_consumer, reducer) = consumer.split_at(0);
+
+                    let origin = ThreadWoker::try_current_index();
+                    let (l, r) = join(
+                        || helper(spliter, false, left, left_consumer),
+                        || {
+                            let stolen = ThreadWoker::try_current_index() != origin;
+                            helper(spliter, stolen, right, right_consumer)
+                        },
+                    );
+
+                    return reducer.reduce(l, r);
+                }
+                (producer, None) => return producer.fold_with(consumer).complete(),
+            }
+        }
+
+        producer.fold_with(consumer).complete()
+    }
+
+    helper(spliter, false, producer, consumer)
 }
@@ -0,0 +1,215 @@
+use core::slice;
+use std::{collections::VecDeque, iter::Chain, mem};
+
+use tracing::{instrument, trace};
+
+use super::vec::{
+    run, Consumer, IndexedParallelIterator, IntoIter, IntoParallelIterator, ParallelIterator,
+    Splitable,
+};
+
+// 环形缓冲区由 front / back 两段连续内存组成，拆分时先在 front 内拆分，越过 front 之后再拆分 back，不需要额外分配内存
+impl<T: Send> IntoParallelIterator for VecDeque<T> {
+    type Item = T;
+    type Iter = IntoIter<T>;
+
+    // Vec::from 会复用 VecDeque 的缓冲区，只在需要时原地移动元素
+    #[instrument(skip_all)]
+    fn into_par_iter(self) -> IntoIter<T> {
+        Vec::from(self).into_par_iter()
+    }
+}
+
+impl<'data, T: Sync + 'data> IntoParallelIterator for &'data VecDeque<T> {
+    type Item = &'data T;
+    type Iter = Iter<'data, T>;
+
+    #[instrument(skip_all)]
+    fn into_par_iter(self) -> Iter<'data, T> {
+        let (front, back) = self.as_slices();
+
+        Iter { front, back }
+    }
+}
+
+impl<'data, T: Send + 'data> IntoParallelIterator for &'data mut VecDeque<T> {
+    type Item = &'data mut T;
+    type Iter = IterMut<'data, T>;
+
+    #[instrument(skip_all)]
+    fn into_par_iter(self) -> IterMut<'data, T> {
+        let (front, back) = self.as_mut_slices();
+
+        IterMut { front, back }
+    }
+}
+
+pub struct Iter<'data, T> {
+    front: &'data [T],
+    back: &'data [T],
+}
+
+impl<'data, T: Sync + 'data> ParallelIterator for Iter<'data, T> {
+    type Item = &'data T;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("vec deque parallel iterator execute");
+
+        run(
+            DequeSplitable {
+                front: self.front,
+                back: self.back,
+            },
+            op,
+        )
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'data, T: Sync + 'data> IndexedParallelIterator for Iter<'data, T> {
+    fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+}
+
+pub struct IterMut<'data, T> {
+    front: &'data mut [T],
+    back: &'data mut [T],
+}
+
+impl<'data, T: Send + 'data> ParallelIterator for IterMut<'data, T> {
+    type Item = &'data mut T;
+
+    fn execute<OP>(self, op: OP) -> OP::Output
+    where
+        OP: Consumer<Self::Item>,
+    {
+        trace!("vec deque mut parallel iterator execute");
+
+        run(
+            DequeMutSplitable {
+                front: self.front,
+                back: self.back,
+            },
+            op,
+        )
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<'data, T: Send + 'data> IndexedParallelIterator for IterMut<'data, T> {
+    fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+}
+
+struct DequeSplitable<'data, T> {
+    front: &'data [T],
+    back: &'data [T],
+}
+
+impl<'data, T: Sync + 'data> Splitable for DequeSplitable<'data, T> {
+    type Item = &'data T;
+
+    type IntoIter = Chain<slice::Iter<'data, T>, slice::Iter<'data, T>>;
+
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        if mid <= self.front.len() {
+            let (left, right) = self.front.split_at(mid);
+
+            (
+                DequeSplitable {
+                    front: left,
+                    back: &[],
+                },
+                DequeSplitable {
+                    front: right,
+                    back: self.back,
+                },
+            )
+        } else {
+            let (left, right) = self.back.split_at(mid - self.front.len());
+
+            (
+                DequeSplitable {
+                    front: self.front,
+                    back: left,
+                },
+                DequeSplitable {
+                    front: right,
+                    back: &[],
+                },
+            )
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.front.iter().chain(self.back)
+    }
+}
+
+struct DequeMutSplitable<'data, T> {
+    front: &'data mut [T],
+    back: &'data mut [T],
+}
+
+impl<'data, T: Send + 'data> Splitable for DequeMutSplitable<'data, T> {
+    type Item = &'data mut T;
+
+    type IntoIter = Chain<slice::IterMut<'data, T>, slice::IterMut<'data, T>>;
+
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let front_len = self.front.len();
+
+        if mid <= front_len {
+            let (left, right) = self.front.split_at_mut(mid);
+
+            (
+                DequeMutSplitable {
+                    front: left,
+                    back: &mut [],
+                },
+                DequeMutSplitable {
+                    front: right,
+                    back: self.back,
+                },
+            )
+        } else {
+            let (left, right) = self.back.split_at_mut(mid - front_len);
+
+            (
+                DequeMutSplitable {
+                    front: self.front,
+                    back: left,
+                },
+                DequeMutSplitable {
+                    front: right,
+                    back: &mut [],
+                },
+            )
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let back = mem::take(&mut self.back);
+
+        self.front.iter_mut().chain(back)
+    }
+}
@@ -1,19 +1,46 @@
 use std::{
+    any::Any,
     cell::UnsafeCell,
-    panic::{catch_unwind, AssertUnwindSafe},
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
     sync::Arc,
 };
 
-use crate::latch::Latch;
+use tracing::error;
+
+use crate::{
+    cancel::{current_token, CancellationToken, TokenGuard},
+    context::JobContext,
+    latch::Latch,
+};
 
 trait Execute {
     unsafe fn execute(this: *const ());
 }
 
+// job 在其它线程中 panic 时，panic 的内容会被保存下来，等待方在拿到结果时重新抛出
+pub enum JobResult<R> {
+    None,
+    Ok(R),
+    Panic(Box<dyn Any + Send>),
+}
+
+impl<R> JobResult<R> {
+    pub fn into_return_value(self) -> R {
+        match self {
+            JobResult::None => unreachable!("job result is not ready"),
+            JobResult::Ok(r) => r,
+            JobResult::Panic(err) => resume_unwind(err),
+        }
+    }
+}
+
+// token 是创建 job 时当前线程的取消 token，job 被其它线程窃取后依然在同一个 token 下执行，context 也是一样
 pub struct Job<F, R> {
     latch: Arc<Latch>,
     task: UnsafeCell<Option<F>>,
-    result: *mut Option<R>,
+    result: *mut JobResult<R>,
+    token: Option<CancellationToken>,
+    context: JobContext,
 }
 
 impl<F, R> Execute for Job<F, R>
@@ -26,29 +53,68 @@ where
         let this = &*this;
 
         let func = (*this.task.get()).take().unwrap();
+        let _guard = TokenGuard::set(this.token.clone());
+        let _context = this.context.enter();
 
-        let r = match catch_unwind(AssertUnwindSafe(func)) {
-            Ok(r) => r,
-            Err(err) => {
-                panic!("panic in job: {:#?}", err)
-            }
+        *this.result = match catch_unwind(AssertUnwindSafe(func)) {
+            Ok(r) => JobResult::Ok(r),
+            Err(err) => JobResult::Panic(err),
         };
 
-        *this.result = Some(r);
         this.latch.set();
     }
 }
 
 impl<F, R> Job<F, R> {
-    pub unsafe fn new(code: F, latch: Arc<Latch>, result: *mut Option<R>) -> Job<F, R> {
+    pub unsafe fn new(code: F, latch: Arc<Latch>, result: *mut JobResult<R>) -> Job<F, R> {
         Job {
             task: UnsafeCell::new(Some(code)),
             latch,
             result,
+            token: current_token(),
+            context: JobContext::capture(),
         }
     }
 }
 
+// 分配在堆上的 job，没有人等待它的结果，执行完后自己释放
+pub struct HeapJob<F> {
+    task: F,
+    context: JobContext,
+}
+
+impl<F> Execute for HeapJob<F>
+where
+    F: FnOnce() + Send,
+{
+    unsafe fn execute(this: *const ()) {
+        let this = Box::from_raw(this as *mut HeapJob<F>);
+        let _context = this.context.enter();
+
+        // 结果需要由 task 自己传递出去，这里只保证 panic 不会让 worker 线程退出
+        if catch_unwind(AssertUnwindSafe(this.task)).is_err() {
+            error!("heap job panicked");
+        }
+    }
+}
+
+impl<F> HeapJob<F>
+where
+    F: FnOnce() + Send,
+{
+    pub fn new(task: F) -> Box<HeapJob<F>> {
+        Box::new(HeapJob {
+            task,
+            context: JobContext::capture(),
+        })
+    }
+
+    // 所有权交给返回的 JobRef，JobRef 必须被执行且只能执行一次
+    pub unsafe fn into_job_ref(self: Box<Self>) -> JobRef {
+        JobRef::new(&*Box::into_raw(self))
+    }
+}
+
 pub struct JobRef {
     f: unsafe fn(*const ()),
     data: *const (),
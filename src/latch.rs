@@ -1,4 +1,8 @@
-use std::sync::atomic::AtomicBool;
+use std::{
+    sync::atomic::AtomicBool,
+    thread,
+    time::{Duration, Instant},
+};
 
 pub struct Latch {
     latch: AtomicBool,
@@ -20,6 +24,25 @@ impl Latch {
         }
     }
 
+    // 在 timeout 内等到 set 时返回 true
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        while !self.probe() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+
+            thread::yield_now();
+        }
+
+        true
+    }
+
+    pub fn probe(&self) -> bool {
+        self.latch.load(std::sync::atomic::Ordering::Acquire)
+    }
+
     pub fn set(&self) {
         self.latch.store(true, std::sync::atomic::Ordering::Release);
     }
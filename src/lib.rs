@@ -1,24 +1,56 @@
 use std::{
     cell::Cell,
     collections::VecDeque,
-    sync::{Arc, Mutex},
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex,
+    },
 };
+mod blocking;
+mod cancel;
+mod context;
+mod deterministic;
 mod job;
 mod latch;
+mod pool;
+mod spawn;
 mod thread_data;
 mod util;
+mod worker_local;
 
-use job::{Job, JobRef};
+use job::{Job, JobRef, JobResult};
 use latch::Latch;
 use thread_data::Root;
+pub use thread_data::{default_num_threads, parse_num_threads, Priority};
 use tracing::{instrument, trace, Level};
 use tracing_subscriber::EnvFilter;
 pub mod iter;
+pub use blocking::{
+    block_in_place, blocking_threads, set_blocking_config, spawn_blocking, BlockingConfig,
+    BlockingHandle,
+};
+pub use cancel::{install_with_token, join_cancellable, CancellationToken, Cancelled, WithCancel};
+pub use context::{task_context, with_task_context};
+pub use deterministic::for_each_seed;
+pub use pool::{install_with_priority, join_timeout, ThreadPool, Timeout};
+pub use spawn::{install_async, spawn, spawn_future, spawn_with_priority, SpawnFuture};
+pub use worker_local::WorkerLocal;
+
+// worker 的生命周期：缩容时 Live 变为 Retiring，worker 执行完自己队列中的 job 后变为 Exited 并退出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum WorkerState {
+    Live,
+    Retiring,
+    Exited,
+}
 
 struct ThreadData {
     queue: Mutex<VecDeque<JobRef>>,
     index: usize,
     crated: Latch,
+    state: AtomicU8,
 }
 
 impl ThreadData {
@@ -27,12 +59,25 @@ impl ThreadData {
             queue: Mutex::new(VecDeque::new()),
             index,
             crated: Latch::new(),
+            state: AtomicU8::new(WorkerState::Live as u8),
         }
     }
 
     fn wait(&self) {
         self.crated.wait();
     }
+
+    fn state(&self) -> WorkerState {
+        match self.state.load(Ordering::Acquire) {
+            0 => WorkerState::Live,
+            1 => WorkerState::Retiring,
+            _ => WorkerState::Exited,
+        }
+    }
+
+    fn set_state(&self, state: WorkerState) {
+        self.state.store(state as u8, Ordering::Release);
+    }
 }
 
 thread_local! {
@@ -41,6 +86,7 @@ thread_local! {
 
 struct ThreadWoker {
     root: Arc<Root>,
+    data: Arc<ThreadData>,
     index: usize,
 }
 
@@ -53,25 +99,38 @@ impl ThreadWoker {
         unsafe { (*Self::current()).index }
     }
 
+    fn try_current_index() -> Option<usize> {
+        let worker = Self::current();
+
+        if worker.is_null() {
+            None
+        } else {
+            Some(unsafe { (*worker).index })
+        }
+    }
+
     fn set_current(&self) {
         trace!("set current: {}", self.index);
         CURRENT_THREAD_WORKER.with(|worker| worker.set(self));
     }
 
     fn push(&self, job: JobRef) {
-        self.root.threads[self.index]
-            .queue
-            .lock()
-            .unwrap()
-            .push_front(job);
+        self.data.queue.lock().unwrap().push_front(job);
     }
 
     fn pop(&self) -> Option<JobRef> {
-        self.root.threads[self.index]
-            .queue
-            .lock()
-            .unwrap()
-            .pop_front()
+        self.data.queue.lock().unwrap().pop_front()
+    }
+}
+
+pub fn current_num_threads() -> usize {
+    let worker = ThreadWoker::current();
+
+    if worker.is_null() {
+        Root::current().num_threads()
+    } else {
+        let worker = unsafe { &*worker };
+        worker.root.num_threads()
     }
 }
 
@@ -93,22 +152,37 @@ where
     unsafe {
         trace!("join on worker: {}", (*worker).index);
 
+        let root = &(*worker).root;
+        if let Some(scheduler) = &root.scheduler {
+            return deterministic::join_serial(scheduler, a, b);
+        }
+
         let latch_b = Arc::new(Latch::new());
-        let mut result_b: Option<R2> = None;
+        let mut result_b = JobResult::None;
         let job_b = Job::new(b, latch_b.clone(), &mut result_b);
         let job_b_ref = JobRef::new(&job_b);
 
         (*worker).push(job_b_ref);
 
-        let r1 = a();
-
-        if let Some(job) = (*worker).pop() {
-            job.execute();
+        // a panic 时 job_b 可能正在其它线程上执行，必须等它结束后才能继续 unwind，否则 job_b 会引用已经释放的栈
+        let r1 = catch_unwind(AssertUnwindSafe(a));
+
+        // a 中 spawn 的 job 也在本地队列的前面，只弹出一次可能拿不到 job_b；
+        // 一直执行本地队列中的 job 直到 job_b 完成，只有一个 worker 时没有人能窃取 job_b
+        while !latch_b.probe() {
+            if let Some(job) = (*worker).pop() {
+                job.execute();
+            } else {
+                std::thread::yield_now();
+            }
         }
 
-        latch_b.wait();
+        let r1 = match r1 {
+            Ok(r1) => r1,
+            Err(err) => resume_unwind(err),
+        };
 
-        (r1, result_b.unwrap())
+        (r1, result_b.into_return_value())
     }
 }
 
@@ -124,25 +198,22 @@ where
         let root = Root::current();
 
         let latch = Arc::new(Latch::new());
-        let mut result_a: Option<R1> = None;
+        let mut result_a = JobResult::None;
         let job = Job::new(a, latch.clone(), &mut result_a);
         let job_a_ref = JobRef::new(&job);
 
-        let mut result_b: Option<R2> = None;
+        let mut result_b = JobResult::None;
         let latch_b = Arc::new(Latch::new());
         let job_b = Job::new(b, latch_b.clone(), &mut result_b);
         let job_b_ref = JobRef::new(&job_b);
 
-        root.state
-            .pending_tasks_job
-            .lock()
-            .unwrap()
-            .extend([job_a_ref, job_b_ref]);
+        root.inject(job_a_ref, Priority::Normal);
+        root.inject(job_b_ref, Priority::Normal);
 
         latch.wait();
         latch_b.wait();
 
-        (result_a.unwrap(), result_b.unwrap())
+        (result_a.into_return_value(), result_b.into_return_value())
     }
 }
 
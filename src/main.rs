@@ -10,14 +10,24 @@ fn main() {
 mod tests {
     use std::{
         collections::HashSet,
-        fmt::Debug,
         thread::{self, sleep},
         time::Duration,
     };
 
     use concurrent_threads::{
-        iter::vec::{IntoParallelIterator, ParallelIterator},
-        join,
+        block_in_place, blocking_threads, current_num_threads, default_num_threads, install_async,
+        install_with_priority, install_with_token,
+        iter::par_bridge::ParallelBridge,
+        iter::slice::{ParallelSlice, ParallelSliceMut},
+        iter::string::ParallelString,
+        iter::unzip::Either,
+        iter::vec::{
+            IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator,
+            IntoParallelRefMutIterator, ParallelDrainRange, ParallelExtend, ParallelIterator,
+        },
+        join, join_cancellable, join_timeout, parse_num_threads, set_blocking_config, spawn,
+        spawn_blocking, spawn_future, spawn_with_priority, BlockingConfig, CancellationToken,
+        Cancelled, Priority, ThreadPool, Timeout, WorkerLocal,
     };
 
     #[test]
@@ -76,40 +86,60 @@ mod tests {
 
     #[test]
     fn sort() {
-        fn quick_sort<T: PartialOrd + Send + Sync + Debug>(v: &mut [T]) {
-            if v.len() <= 1 {
-                return;
-            }
+        let data: Vec<u32> = (0..100_000).map(|_| rand::random::<u32>() % 1000).collect();
+        let mut expected = data.clone();
+        expected.sort();
 
-            let mid = partition(v);
-            let (lo, hi) = v.split_at_mut(mid);
-            join(|| quick_sort(lo), || quick_sort(hi));
-        }
+        let mut v = data.clone();
+        v.par_sort();
+        assert_eq!(v, expected);
 
-        fn partition<T: PartialOrd + Send + Sync>(v: &mut [T]) -> usize {
-            let pivot = v.len() - 1;
-            let mut i = 0;
-            for j in 0..pivot {
-                if v[j] <= v[pivot] {
-                    v.swap(i, j);
-                    i += 1;
-                }
-            }
-            v.swap(i, pivot);
-            i
-        }
+        let mut v = data.clone();
+        v.par_sort_unstable();
+        assert_eq!(v, expected);
 
-        let mut data: Vec<_> = (0..10 * 1024)
-            .map(|_| rand::random::<u16>())
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect();
+        // 已经有序、逆序和全部相等的输入
+        let mut v: Vec<u32> = (0..100_000).collect();
+        v.par_sort_unstable();
+        assert!(v.windows(2).all(|w| w[0] <= w[1]));
+        v.par_sort_unstable_by(|a, b| b.cmp(a));
+        assert!(v.windows(2).all(|w| w[0] >= w[1]));
+        let mut v = vec![7u32; 100_000];
+        v.par_sort_unstable();
+        assert!(v.iter().all(|&i| i == 7));
 
-        quick_sort(&mut data);
-        let mut sorted_data = data.clone();
-        sorted_data.sort();
+        // 稳定排序：key 相等的元素保持原来的顺序
+        let pairs: Vec<(u32, usize)> = data.iter().copied().zip(0..).collect();
+        let mut v = pairs.clone();
+        v.par_sort_by_key(|&(key, _)| key);
+        assert!(v.windows(2).all(|w| w[0] <= w[1]));
+        let mut v = pairs;
+        v.par_sort_by_cached_key(|&(key, _)| key.to_string());
+        let mut expected = v.clone();
+        expected.sort_by_key(|&(key, _)| key.to_string());
+        assert_eq!(v, expected);
 
-        assert_eq!(data, sorted_data);
+        // 带 Drop 的类型
+        let mut v: Vec<String> = data.iter().map(|i| i.to_string()).collect();
+        let mut expected = v.clone();
+        expected.sort();
+        v.par_sort_by(|a, b| a.cmp(b));
+        assert_eq!(v, expected);
+
+        // compare panic 后每个元素依然只出现一次
+        let mut v: Vec<String> = data.iter().map(|i| i.to_string()).collect();
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            v.par_sort_by(|a, b| {
+                if calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed) == 500_000 {
+                    panic!("compare panic");
+                }
+                a.cmp(b)
+            });
+        }));
+        assert!(result.is_err());
+        v.sort();
+        assert_eq!(v, expected);
     }
 
     #[test]
@@ -134,4 +164,1300 @@ mod tests {
 
         assert!(v == vec![4, 8, 12]);
     }
+
+    #[test]
+    fn par_iter() {
+        let items: Vec<_> = (0..1000).map(|i| i.to_string()).collect();
+
+        let lens = items.par_iter().map(|s| s.len()).collect::<Vec<_>>();
+        let expected = items.iter().map(|s| s.len()).collect::<Vec<_>>();
+        assert_eq!(lens, expected);
+
+        let refs = items[10..20].par_iter().collect::<Vec<_>>();
+        assert_eq!(refs, items[10..20].iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn par_iter_mut() {
+        let mut items: Vec<_> = (0..1000).collect();
+
+        items.par_iter_mut().for_each(|i| *i *= 2);
+        assert_eq!(items, (0..1000).map(|i| i * 2).collect::<Vec<_>>());
+
+        items[..500].par_iter_mut().for_each(|i| *i = 0);
+        assert!(items[..500].iter().all(|i| *i == 0));
+        assert_eq!(items[500], 1000);
+    }
+
+    #[test]
+    fn par_chunks() {
+        let items: Vec<_> = (0..1003).collect();
+
+        let sums = items
+            .par_chunks(10)
+            .map(|c| c.iter().sum::<i32>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sums,
+            items
+                .chunks(10)
+                .map(|c| c.iter().sum::<i32>())
+                .collect::<Vec<_>>()
+        );
+
+        let exact = items.par_chunks_exact(10);
+        assert_eq!(exact.remainder(), &[1000, 1001, 1002]);
+        assert_eq!(
+            exact.collect::<Vec<_>>(),
+            items.chunks_exact(10).collect::<Vec<_>>()
+        );
+
+        let rchunks = items.par_rchunks(7).collect::<Vec<_>>();
+        assert_eq!(rchunks, items.rchunks(7).collect::<Vec<_>>());
+
+        let windows = items.par_windows(5).collect::<Vec<_>>();
+        assert_eq!(windows, items.windows(5).collect::<Vec<_>>());
+        assert_eq!(items[..3].par_windows(5).len(), 0);
+    }
+
+    #[test]
+    fn par_chunks_mut() {
+        let mut items = vec![0; 1003];
+
+        items.par_chunks_mut(10).for_each(|chunk| {
+            let len = chunk.len();
+            chunk.iter_mut().for_each(|i| *i = len);
+        });
+
+        assert!(items[..1000].iter().all(|i| *i == 10));
+        assert!(items[1000..].iter().all(|i| *i == 3));
+    }
+
+    #[test]
+    fn range() {
+        let v = (0..1000u32)
+            .into_par_iter()
+            .map(|i| i * 2)
+            .collect::<Vec<_>>();
+        assert_eq!(v, (0..1000u32).map(|i| i * 2).collect::<Vec<_>>());
+
+        let v = (-128..=127i8).into_par_iter().collect::<Vec<_>>();
+        assert_eq!(v, (-128..=127i8).collect::<Vec<_>>());
+
+        let v = (250..=u8::MAX).into_par_iter().collect::<Vec<_>>();
+        assert_eq!(v, vec![250, 251, 252, 253, 254, 255]);
+
+        let (start, end) = (10i64, 5i64);
+        assert_eq!((start..end).into_par_iter().opt_len(), Some(0));
+        assert_eq!((0..=u8::MAX).into_par_iter().len(), 256);
+
+        // 长度超过 usize 的 range 没有 opt_len，按非 indexed 的方式执行
+        assert_eq!((0..=u128::MAX).into_par_iter().opt_len(), None);
+        assert_eq!((i128::MIN..i128::MAX).into_par_iter().opt_len(), None);
+        let first =
+            (0..=u128::MAX)
+                .into_par_iter()
+                .try_for_each(|i| if i >= 1000 { Err(i) } else { Ok(()) });
+        assert!(first.is_err());
+
+        // 类型很宽但长度很短的 range 依然可以 collect
+        let v = (u64::MAX - 10..=u64::MAX)
+            .into_par_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(v, (u64::MAX - 10..=u64::MAX).collect::<Vec<_>>());
+        let v = (i128::MIN..i128::MIN + 1000)
+            .into_par_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(v, (i128::MIN..i128::MIN + 1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn with_min_max_len() {
+        use std::sync::Mutex;
+
+        // map_init 的状态每个叶子一个，drop 时记录这个叶子处理的 item 数
+        struct Leaf<'a> {
+            len: usize,
+            lens: &'a Mutex<Vec<usize>>,
+        }
+        impl Drop for Leaf<'_> {
+            fn drop(&mut self) {
+                self.lens.lock().unwrap().push(self.len);
+            }
+        }
+
+        let items: Vec<_> = (0..1000).collect();
+        let lens = Mutex::new(Vec::new());
+        let v = items
+            .par_chunks(1)
+            .with_max_len(10)
+            .map_init(
+                || Leaf {
+                    len: 0,
+                    lens: &lens,
+                },
+                |leaf, chunk| {
+                    leaf.len += 1;
+                    chunk[0]
+                },
+            )
+            .collect::<Vec<_>>();
+        assert_eq!(v, items);
+        // 每个叶子最多 10 个 item
+        let lens = lens.into_inner().unwrap();
+        assert_eq!(lens.iter().sum::<usize>(), 1000);
+        assert!(lens.iter().all(|&len| len <= 10), "{lens:?}");
+
+        // 每个叶子至少 2 个 chunk（500 个 item），最多只能拆成两半
+        let lens = Mutex::new(Vec::new());
+        items
+            .par_chunks(250)
+            .with_min_len(2)
+            .map_init(
+                || Leaf {
+                    len: 0,
+                    lens: &lens,
+                },
+                |leaf, chunk| {
+                    leaf.len += 1;
+                    chunk.len()
+                },
+            )
+            .for_each(|_| {});
+        let lens = lens.into_inner().unwrap();
+        assert_eq!(lens.iter().sum::<usize>(), 4);
+        assert!(lens.iter().all(|&len| len >= 2), "{lens:?}");
+
+        // 长度未知的迭代器忽略 min / max 的限制，但结果依然完整
+        let text = "0123456789".repeat(100);
+        let v = text.par_chars().with_max_len(10).collect::<Vec<_>>();
+        assert_eq!(v, text.chars().collect::<Vec<_>>());
+        let v = text.par_chars().with_min_len(500).collect::<Vec<_>>();
+        assert_eq!(v, text.chars().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn into_par_iter_drop() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Droppable(#[allow(dead_code)] String);
+
+        impl Drop for Droppable {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let items: Vec<_> = (0..1000).map(|i| i.to_string()).collect();
+        let lens = items.into_par_iter().map(|s| s.len()).collect::<Vec<_>>();
+        assert_eq!(lens.iter().sum::<usize>(), 2890);
+
+        let items: Vec<_> = (0..1000).map(|i| Droppable(i.to_string())).collect();
+        items.into_par_iter().for_each(drop);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 1000);
+    }
+
+    #[test]
+    fn par_drain() {
+        let mut items: Vec<_> = (0..100).map(|i| i.to_string()).collect();
+
+        let drained = items.par_drain(10..90).collect::<Vec<_>>();
+        assert_eq!(drained, (10..90).map(|i| i.to_string()).collect::<Vec<_>>());
+        assert_eq!(
+            items,
+            (0..10)
+                .chain(90..100)
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+        );
+
+        // 没有执行的 drain 在 drop 时依然移除 range 内的元素
+        drop(items.par_drain(..5));
+        assert_eq!(items.len(), 15);
+
+        let drained = items.par_drain(..).collect::<Vec<_>>();
+        assert_eq!(drained.len(), 15);
+        assert!(items.is_empty());
+    }
+
+    #[test]
+    fn collect_panic() {
+        use std::{
+            panic::{catch_unwind, AssertUnwindSafe},
+            sync::atomic::{AtomicUsize, Ordering},
+        };
+
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Droppable(usize);
+
+        impl Drop for Droppable {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let items: Vec<_> = (0..1000).map(Droppable).collect();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            items
+                .into_par_iter()
+                .map(|item| {
+                    if item.0 == 500 {
+                        panic!("map panic");
+                    }
+                    item
+                })
+                .collect::<Vec<_>>()
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(DROPS.load(Ordering::Relaxed), 1000);
+
+        // panic 之后线程池依然可用
+        let v = vec![1, 2, 3]
+            .into_par_iter()
+            .map(|i| i + 1)
+            .collect::<Vec<_>>();
+        assert_eq!(v, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn collect_collections() {
+        use std::collections::{BTreeMap, BTreeSet, HashMap, LinkedList, VecDeque};
+
+        let map = (0..1000u32)
+            .into_par_iter()
+            .map(|i| (i, i.to_string()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map[&42], "42");
+
+        let set = (0..1000u32)
+            .into_par_iter()
+            .map(|i| i % 10)
+            .collect::<HashSet<_>>();
+        assert_eq!(set.len(), 10);
+
+        let tree = (0..100u32)
+            .into_par_iter()
+            .map(|i| (i, i * 2))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            tree.values().copied().collect::<Vec<_>>(),
+            (0..100).map(|i| i * 2).collect::<Vec<_>>()
+        );
+
+        let tree_set = (0..100u32).into_par_iter().collect::<BTreeSet<_>>();
+        assert_eq!(tree_set.len(), 100);
+
+        let deque = (0..100u32).into_par_iter().collect::<VecDeque<_>>();
+        assert!(deque.iter().copied().eq(0..100));
+
+        let list = (0..100u32).into_par_iter().collect::<LinkedList<_>>();
+        assert!(list.iter().copied().eq(0..100));
+
+        let chars: Vec<_> = "hello world".chars().collect();
+        let s = chars
+            .par_iter()
+            .map(|c| c.to_ascii_uppercase())
+            .collect::<String>();
+        assert_eq!(s, "HELLO WORLD");
+
+        let words = vec!["a", "b", "c"];
+        let s = words.into_par_iter().collect::<String>();
+        assert_eq!(s, "abc");
+
+        let s = (0..10i32)
+            .into_par_iter()
+            .map(|i| i.to_string())
+            .collect::<String>();
+        assert_eq!(s, "0123456789");
+
+        let boxed = (0..10i32).into_par_iter().collect::<Box<[i32]>>();
+        assert_eq!(&*boxed, &(0..10).collect::<Vec<_>>()[..]);
+
+        (0..10i32).into_par_iter().map(|_| ()).collect::<()>();
+
+        let mut map = HashMap::new();
+        map.insert(1000u32, "1000".to_string());
+        map.parallel_extend((0..10u32).into_par_iter().map(|i| (i, i.to_string())));
+        assert_eq!(map.len(), 11);
+    }
+
+    #[test]
+    fn collect_result() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let v: Result<Vec<_>, String> = (0..1000u32).into_par_iter().map(Ok).collect();
+        assert_eq!(v.unwrap(), (0..1000).collect::<Vec<_>>());
+
+        let processed = AtomicUsize::new(0);
+        let v: Result<Vec<_>, String> = (0..100_000u32)
+            .into_par_iter()
+            .map(|i| {
+                processed.fetch_add(1, Ordering::Relaxed);
+                if i % 1000 == 999 {
+                    Err(format!("bad {i}"))
+                } else {
+                    Ok(i)
+                }
+            })
+            .collect();
+        assert!(v.unwrap_err().starts_with("bad "));
+        assert!(processed.load(Ordering::Relaxed) < 100_000);
+
+        let v: Option<HashSet<_>> = (0..100u32).into_par_iter().map(Some).collect();
+        assert_eq!(v.unwrap().len(), 100);
+
+        let v: Option<Vec<_>> = (0..100u32)
+            .into_par_iter()
+            .map(|i| if i == 50 { None } else { Some(i) })
+            .collect();
+        assert!(v.is_none());
+    }
+
+    #[test]
+    fn try_ops() {
+        use std::ops::ControlFlow;
+
+        let r: Result<(), u32> =
+            (0..1000u32)
+                .into_par_iter()
+                .try_for_each(|i| if i == 500 { Err(i) } else { Ok(()) });
+        assert_eq!(r, Err(500));
+
+        let r: Option<()> = (0..1000u32).into_par_iter().try_for_each(|_| Some(()));
+        assert_eq!(r, Some(()));
+
+        let sum = (0..1000u64)
+            .into_par_iter()
+            .try_fold(|| 0u64, |acc, i| acc.checked_add(i))
+            .try_reduce(|| 0, |a, b| a.checked_add(b));
+        assert_eq!(sum, Some(499_500));
+
+        let overflow = (0..=u8::MAX)
+            .into_par_iter()
+            .try_fold(|| 0u8, |acc, i| acc.checked_add(i))
+            .try_reduce(|| 0, |a, b| a.checked_add(b));
+        assert_eq!(overflow, None);
+
+        let flow = (0..1000u32).into_par_iter().try_for_each(|i| {
+            if i == 10 {
+                ControlFlow::Break(i)
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(flow, ControlFlow::Break(10));
+    }
+
+    #[test]
+    fn unzip_partition() {
+        let (a, b): (Vec<u32>, Vec<u64>) = (0..1000u32)
+            .into_par_iter()
+            .map(|i| (i, i as u64 * 2))
+            .unzip();
+        assert_eq!(a, (0..1000).collect::<Vec<u32>>());
+        assert_eq!(b, (0..1000).map(|i| i * 2).collect::<Vec<u64>>());
+
+        let (even, odd): (Vec<u32>, HashSet<u32>) =
+            (0..1000u32).into_par_iter().partition(|i| i % 2 == 0);
+        assert_eq!(even, (0..1000).step_by(2).collect::<Vec<u32>>());
+        assert_eq!(odd, (1..1000).step_by(2).collect::<HashSet<u32>>());
+
+        let (small, large): (Vec<u32>, Vec<String>) =
+            (0..1000u32).into_par_iter().partition_map(|i| {
+                if i < 10 {
+                    Either::Left(i)
+                } else {
+                    Either::Right(i.to_string())
+                }
+            });
+        assert_eq!(small, (0..10).collect::<Vec<u32>>());
+        assert_eq!(large.len(), 990);
+        assert_eq!(large[0], "10");
+    }
+
+    #[test]
+    fn par_bridge() {
+        use std::io::{BufRead, Cursor};
+
+        let text: String = (0..1000).map(|i| format!("{i}\n")).collect();
+        let mut lines: Vec<u32> = Cursor::new(text)
+            .lines()
+            .par_bridge()
+            .map(|line| line.unwrap().parse().unwrap())
+            .collect();
+        lines.sort();
+        assert_eq!(lines, (0..1000).collect::<Vec<u32>>());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let producer = thread::spawn(move || {
+            for i in 0..1000u64 {
+                tx.send(i).unwrap();
+            }
+        });
+        let sum: Option<u64> = rx
+            .into_iter()
+            .par_bridge()
+            .map(Some)
+            .try_reduce(|| 0, |a, b| Some(a + b));
+        producer.join().unwrap();
+        assert_eq!(sum, Some(499_500));
+    }
+
+    #[test]
+    fn map_with_init() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let v: Vec<u32> = (0..1000u32)
+            .into_par_iter()
+            .map_with(Vec::new(), |buf: &mut Vec<u32>, i| {
+                buf.push(i);
+                i * 2
+            })
+            .collect();
+        assert_eq!(v, (0..1000).map(|i| i * 2).collect::<Vec<u32>>());
+
+        let inits = AtomicUsize::new(0);
+        let v: Vec<String> = (0..1000u32)
+            .into_par_iter()
+            .with_min_len(100)
+            .map_init(
+                || {
+                    inits.fetch_add(1, Ordering::Relaxed);
+                    String::new()
+                },
+                |buf, i| {
+                    buf.clear();
+                    buf.push_str(&i.to_string());
+                    buf.clone()
+                },
+            )
+            .collect();
+        assert_eq!(v[999], "999");
+        assert!(inits.load(Ordering::Relaxed) <= 10);
+
+        // 逐个 consume 的迭代器：整个叶子只调用一次 init
+        struct OneByOne(Vec<u32>);
+        impl ParallelIterator for OneByOne {
+            type Item = u32;
+
+            fn execute<OP>(self, op: OP) -> OP::Output
+            where
+                OP: concurrent_threads::iter::vec::Consumer<u32>,
+            {
+                self.0
+                    .into_iter()
+                    .fold(op, |consumer, item| consumer.consume(item))
+                    .complete()
+            }
+        }
+        let inits = AtomicUsize::new(0);
+        let v: Vec<u32> = OneByOne((0..100).collect())
+            .map_init(|| inits.fetch_add(1, Ordering::Relaxed), |_, i| i)
+            .collect();
+        assert_eq!(v, (0..100).collect::<Vec<u32>>());
+        assert_eq!(inits.load(Ordering::Relaxed), 1);
+
+        // par_bridge 的叶子多次 consume_iter，也只调用一次 init
+        let inits = AtomicUsize::new(0);
+        let v: Vec<u32> = (0..1000u32)
+            .par_bridge()
+            .map_init(|| inits.fetch_add(1, Ordering::Relaxed), |_, i| i)
+            .collect();
+        assert_eq!(v.len(), 1000);
+        assert!(inits.load(Ordering::Relaxed) <= current_num_threads());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        (0..1000u32)
+            .into_par_iter()
+            .for_each_with(tx, |tx, i| tx.send(i).unwrap());
+        let mut received: Vec<u32> = rx.iter().collect();
+        received.sort();
+        assert_eq!(received, (0..1000).collect::<Vec<u32>>());
+
+        let count = AtomicUsize::new(0);
+        (0..1000u32).into_par_iter().for_each_init(
+            || 1,
+            |step, _| {
+                count.fetch_add(*step, Ordering::Relaxed);
+            },
+        );
+        assert_eq!(count.load(Ordering::Relaxed), 1000);
+
+        let r = (0..1000u32)
+            .into_par_iter()
+            .try_for_each_with(500, |limit, i| if i < *limit { Ok(()) } else { Err(i) });
+        assert!(matches!(r, Err(i) if i >= 500));
+
+        let r: Option<()> = (0..1000u32)
+            .into_par_iter()
+            .try_for_each_init(|| 1000, |limit, i| (i < *limit).then_some(()));
+        assert_eq!(r, Some(()));
+    }
+
+    #[test]
+    fn par_string() {
+        let text: String = (0..5000)
+            .map(|i| format!("{i} 日志 ünïcödé,  line\t{i}\r\n"))
+            .collect::<String>()
+            + ",,trailing,";
+
+        let chars: Vec<char> = text.par_chars().collect();
+        assert_eq!(chars, text.chars().collect::<Vec<_>>());
+
+        let indices: Vec<(usize, char)> = text.par_char_indices().collect();
+        assert_eq!(indices, text.char_indices().collect::<Vec<_>>());
+
+        let bytes: Vec<u8> = text.par_bytes().collect();
+        assert_eq!(bytes, text.as_bytes());
+
+        let lines: Vec<&str> = text.par_lines().collect();
+        assert_eq!(lines, text.lines().collect::<Vec<_>>());
+
+        let split: Vec<&str> = text.par_split(',').collect();
+        assert_eq!(split, text.split(',').collect::<Vec<_>>());
+
+        let split: Vec<&str> = text.par_split_terminator(',').collect();
+        assert_eq!(split, text.split_terminator(',').collect::<Vec<_>>());
+
+        let split: Vec<&str> = text.par_split(['日', '\n']).collect();
+        assert_eq!(split, text.split(['日', '\n']).collect::<Vec<_>>());
+
+        let split: Vec<&str> = text.par_split(|c: char| c.is_ascii_digit()).collect();
+        assert_eq!(
+            split,
+            text.split(|c: char| c.is_ascii_digit()).collect::<Vec<_>>()
+        );
+
+        let words: Vec<&str> = text.par_split_whitespace().collect();
+        assert_eq!(words, text.split_whitespace().collect::<Vec<_>>());
+
+        assert_eq!("".par_split(',').collect::<Vec<_>>(), vec![""]);
+        assert!("".par_lines().collect::<Vec<_>>().is_empty());
+        // 最后一行没有 "\n" 时保留末尾的 '\r'
+        for text in ["a\r", "a\r\nb\r", "a\n\r", "\r\n\r"] {
+            let lines: Vec<&str> = text.par_lines().collect();
+            assert_eq!(lines, text.lines().collect::<Vec<_>>(), "{text:?}");
+        }
+        assert!("a\n".par_split_terminator('\n').collect::<Vec<_>>() == vec!["a"]);
+    }
+
+    #[test]
+    fn collections_par_iter() {
+        use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, LinkedList, VecDeque};
+
+        // push_front 让数据分布在环形缓冲区的两段中
+        let mut deque: VecDeque<u32> = (500..1000).collect();
+        for i in (0..500).rev() {
+            deque.push_front(i);
+        }
+        assert!(!deque.as_slices().1.is_empty());
+        let v: Vec<u32> = deque.par_iter().map(|&i| i).collect();
+        assert_eq!(v, (0..1000).collect::<Vec<u32>>());
+        deque.par_iter_mut().for_each(|i| *i *= 2);
+        assert!(deque.iter().copied().eq((0..1000).map(|i| i * 2)));
+        let v: Vec<u32> = deque.into_par_iter().collect();
+        assert_eq!(v, (0..1000).map(|i| i * 2).collect::<Vec<u32>>());
+
+        let mut map: HashMap<u32, String> = (0..1000).map(|i| (i, i.to_string())).collect();
+        map.par_iter_mut().for_each(|(_, v)| v.push('!'));
+        let total: usize = map
+            .par_iter()
+            .map(|(_, v)| v.len())
+            .collect::<Vec<_>>()
+            .iter()
+            .sum();
+        assert_eq!(total, map.values().map(String::len).sum());
+        let back: HashMap<u32, String> = map.clone().into_par_iter().collect();
+        assert_eq!(back, map);
+        let keys: HashSet<u32> = map.keys().copied().collect();
+        let mut v: Vec<&u32> = keys.par_iter().collect();
+        v.sort();
+        assert_eq!(v.len(), 1000);
+
+        let mut tree: BTreeMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        tree.par_iter_mut().for_each(|(k, v)| *v += k);
+        let v: Vec<(&u32, &u32)> = tree.par_iter().collect();
+        assert!(v.iter().all(|(k, v)| **v == **k * 2));
+        let set: BTreeSet<u32> = (0..1000).collect();
+        let v: Vec<u32> = set.into_par_iter().collect();
+        assert_eq!(v, (0..1000).collect::<Vec<u32>>());
+
+        let heap: BinaryHeap<u32> = (0..1000).collect();
+        let mut v: Vec<u32> = heap.into_par_iter().collect();
+        v.sort();
+        assert_eq!(v, (0..1000).collect::<Vec<u32>>());
+
+        let mut list: LinkedList<String> = (0..100).map(|i| i.to_string()).collect();
+        list.par_iter_mut().for_each(|s| s.push('x'));
+        let v: Vec<String> = list.into_par_iter().collect();
+        assert_eq!(v[42], "42x");
+
+        let mut opt = Some(String::from("a"));
+        opt.par_iter_mut().for_each(|s| s.push('b'));
+        assert_eq!(opt.par_iter().collect::<Vec<_>>(), vec!["ab"]);
+        assert_eq!(opt.into_par_iter().collect::<Vec<_>>(), vec!["ab"]);
+        assert!(None::<u32>.into_par_iter().collect::<Vec<_>>().is_empty());
+    }
+
+    #[test]
+    fn par_scan() {
+        let data: Vec<u64> = (0..100_000).map(|_| rand::random::<u64>() % 1000).collect();
+        let expected: Vec<u64> = data
+            .iter()
+            .scan(0, |acc, &x| {
+                *acc += x;
+                Some(*acc)
+            })
+            .collect();
+
+        let sums = data.par_iter().map(|&x| x).par_scan(|| 0, |a, b| a + b);
+        assert_eq!(sums, expected);
+        let sums = data.into_par_iter().par_scan(|| 0, |a, b| a + b);
+        assert_eq!(sums, expected);
+
+        // 满足结合律但不满足交换律的 op，检查块之间的顺序
+        let prefixes = (0..2000u32)
+            .into_par_iter()
+            .map(|i| (i % 10).to_string())
+            .par_scan(String::new, |a, b| a.clone() + b);
+        assert_eq!(prefixes.len(), 2000);
+        assert!(prefixes.windows(2).all(|w| w[1].starts_with(&w[0])));
+        assert_eq!(prefixes[1999].len(), 2000);
+
+        assert!(Vec::<u32>::new()
+            .into_par_iter()
+            .par_scan(|| 0, |a, b| a + b)
+            .is_empty());
+    }
+
+    // 最小的本地执行器：poll 返回 Pending 时 park 当前线程，直到 waker 唤醒
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        use std::{
+            sync::Arc,
+            task::{Context, Poll, Wake},
+        };
+
+        struct ThreadWaker(thread::Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn async_bridge() {
+        let sum = block_on(spawn_future(|| {
+            let (a, b) = join(|| (0..500u64).sum::<u64>(), || (500..1000u64).sum::<u64>());
+            a + b
+        }));
+        assert_eq!(sum, 499_500);
+
+        let v = block_on(install_async(|| {
+            (0..1000u32)
+                .into_par_iter()
+                .map(|i| i * 2)
+                .collect::<Vec<_>>()
+        }));
+        assert_eq!(v[999], 1998);
+
+        let r = std::panic::catch_unwind(|| block_on(spawn_future(|| panic!("spawned panic"))));
+        assert!(r.is_err());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        for i in 0..10u32 {
+            let tx = tx.clone();
+            spawn(move || tx.send(i).unwrap());
+        }
+        drop(tx);
+        let mut received: Vec<u32> = rx.iter().collect();
+        received.sort();
+        assert_eq!(received, (0..10).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn cancellation() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let token = CancellationToken::new();
+        let processed = AtomicUsize::new(0);
+        let r: Result<Vec<String>, Cancelled> = (0..10_000_000u64)
+            .into_par_iter()
+            .map(|i| {
+                if processed.fetch_add(1, Ordering::Relaxed) == 1000 {
+                    token.cancel();
+                }
+                i.to_string()
+            })
+            .with_cancel(&token)
+            .collect();
+        assert_eq!(r, Err(Cancelled));
+        assert!(processed.load(Ordering::Relaxed) < 5_000_000);
+
+        let token = CancellationToken::new();
+        let r: Result<Vec<u32>, Cancelled> =
+            (0..1000u32).into_par_iter().with_cancel(&token).collect();
+        assert_eq!(r.unwrap().len(), 1000);
+        assert_eq!(
+            (0..1000u32)
+                .into_par_iter()
+                .with_cancel(&token)
+                .for_each(|_| {}),
+            Ok(())
+        );
+
+        // par_scan 和 par_bridge 同样检查 token
+        let scan_token = CancellationToken::new();
+        let r = install_with_token(&scan_token, || {
+            (0..10_000_000usize)
+                .into_par_iter()
+                .map(|i| {
+                    if i == 1000 {
+                        scan_token.cancel();
+                    }
+                    i
+                })
+                .par_scan(|| 0, |a, b| a + b)
+        });
+        assert_eq!(r, Err(Cancelled));
+
+        let bridge_token = CancellationToken::new();
+        let bridged = AtomicUsize::new(0);
+        let r = (0..10_000_000u64)
+            .par_bridge()
+            .with_cancel(&bridge_token)
+            .for_each(|_| {
+                if bridged.fetch_add(1, Ordering::Relaxed) == 1000 {
+                    bridge_token.cancel();
+                }
+            });
+        assert_eq!(r, Err(Cancelled));
+        assert!(bridged.load(Ordering::Relaxed) < 5_000_000);
+
+        // 被取消的 collect 不会 panic，也不会返回只写入了一部分的 vec
+        let collect_token = CancellationToken::new();
+        let r = install_with_token(&collect_token, || {
+            (0..10_000_000u64)
+                .into_par_iter()
+                .map(|i| {
+                    if i == 1000 {
+                        collect_token.cancel();
+                    }
+                    i
+                })
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(r, Err(Cancelled));
+
+        token.cancel();
+        let ran = AtomicUsize::new(0);
+        let r = install_with_token(&token, || ran.fetch_add(1, Ordering::Relaxed));
+        assert_eq!(r, Err(Cancelled));
+        assert_eq!(ran.load(Ordering::Relaxed), 0);
+
+        // 递归的算法在取消后尽快结束
+        fn count(
+            depth: u32,
+            nodes: &AtomicUsize,
+            token: &CancellationToken,
+        ) -> Result<(), Cancelled> {
+            if nodes.fetch_add(1, Ordering::Relaxed) == 100 {
+                token.cancel();
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+            let (a, b) = join_cancellable(
+                || count(depth - 1, nodes, token),
+                || count(depth - 1, nodes, token),
+            )?;
+            a.and(b)
+        }
+
+        let token = CancellationToken::new();
+        let nodes = AtomicUsize::new(0);
+        let r = install_with_token(&token, || count(20, &nodes, &token));
+        assert_eq!(r, Err(Cancelled));
+        assert!(nodes.load(Ordering::Relaxed) < 1 << 20);
+    }
+
+    #[test]
+    fn spawn_in_join_single_worker() {
+        // a 中 spawn 的 job 排在 job_b 前面，只有一个 worker 时 join 必须自己执行到 job_b
+        let pool = ThreadPool::new(1);
+        let r = pool.install_timeout(
+            || {
+                join(
+                    || {
+                        spawn(|| {});
+                        1
+                    },
+                    || 2,
+                )
+            },
+            Duration::from_secs(10),
+        );
+        assert_eq!(r, Ok((1, 2)));
+    }
+
+    #[test]
+    fn thread_pool_timeout() {
+        let pool = ThreadPool::new(2);
+        assert_eq!(pool.current_num_threads(), 2);
+        assert_eq!(pool.install(current_num_threads), 2);
+
+        let v: Vec<u32> = (0..1000).collect();
+        let doubled: Vec<u32> = pool.install(|| v.par_iter().map(|x| x * 2).collect());
+        assert_eq!(doubled, v.iter().map(|x| x * 2).collect::<Vec<_>>());
+
+        // 超时后 job 依然在线程池中执行完，不会访问调用方的栈
+        let r = pool.install_timeout(
+            || sleep(Duration::from_millis(500)),
+            Duration::from_millis(10),
+        );
+        assert_eq!(r, Err(Timeout));
+
+        let r = pool.install_timeout(|| 42, Duration::from_secs(10));
+        assert_eq!(r, Ok(42));
+
+        let r = join_timeout(
+            || sleep(Duration::from_millis(500)),
+            || 1,
+            Duration::from_millis(10),
+        );
+        assert_eq!(r, Err(Timeout));
+
+        let r = join_timeout(|| 1, || "b", Duration::from_secs(10));
+        assert_eq!(r, Ok((1, "b")));
+
+        // 线程池 drop 时队列中还没有执行的 job 依然会执行完，其中的 Sender 都会被 drop
+        let pool = ThreadPool::new(1);
+        let (gate_tx, gate_rx) = std::sync::mpsc::channel::<()>();
+        let (tx, rx) = std::sync::mpsc::channel();
+        pool.spawn_with_priority(Priority::Normal, move || gate_rx.recv().unwrap());
+        for i in 0..100 {
+            let tx = tx.clone();
+            pool.spawn_with_priority(Priority::Normal, move || tx.send(i).unwrap());
+        }
+        let timeout_tx = tx.clone();
+        let r = pool.install_timeout(move || timeout_tx.send(100).unwrap(), Duration::ZERO);
+        assert_eq!(r, Err(Timeout));
+        drop(tx);
+        drop(pool);
+
+        gate_tx.send(()).unwrap();
+        // 所有的 Sender 被 drop 后 recv_timeout 立即返回 Err，job 被丢弃时在超时后返回
+        let mut received = Vec::new();
+        while let Ok(i) = rx.recv_timeout(Duration::from_secs(10)) {
+            received.push(i);
+        }
+        received.sort();
+        assert_eq!(received, (0..=100).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn priority() {
+        use std::sync::{mpsc, Arc, Mutex};
+
+        // 单线程的线程池，先用一个 job 占住 worker，再放入不同优先级的 job
+        let pool = ThreadPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.spawn_with_priority(Priority::Normal, move || {
+            started_tx.send(()).unwrap();
+            rx.recv().unwrap();
+        });
+        started_rx.recv().unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let push = |priority, i| {
+            let order = order.clone();
+            pool.spawn_with_priority(priority, move || order.lock().unwrap().push(i));
+        };
+
+        push(Priority::Low, 0);
+        push(Priority::Normal, 1);
+        for i in 2..42 {
+            push(Priority::High, i);
+        }
+        tx.send(()).unwrap();
+
+        while order.lock().unwrap().len() < 42 {
+            sleep(Duration::from_millis(1));
+        }
+
+        // 高优先级的 job 先执行，但是不会让低优先级的 job 一直等待
+        let order = order.lock().unwrap();
+        let high: Vec<_> = order.iter().copied().filter(|&i| i >= 2).collect();
+        assert_eq!(high, (2..42).collect::<Vec<_>>());
+        let low = order.iter().position(|&i| i == 0).unwrap();
+        let normal = order.iter().position(|&i| i == 1).unwrap();
+        // 占住 worker 的 job 是第 1 个，之后每第 16 个 job 从低优先级开始查找，空闲的轮询不计入
+        assert_eq!((low, normal), (14, 30), "{order:?}");
+
+        assert_eq!(install_with_priority(Priority::High, || 1 + 1), 2);
+        let (tx, rx) = mpsc::channel();
+        spawn_with_priority(Priority::Low, move || tx.send(3).unwrap());
+        assert_eq!(rx.recv().unwrap(), 3);
+    }
+
+    #[test]
+    fn blocking() {
+        use std::{
+            sync::atomic::{AtomicUsize, Ordering},
+            time::Instant,
+        };
+
+        // 阻塞线程池的配置是全局的，结束时（包括 panic）恢复默认配置
+        struct RestoreConfig;
+        impl Drop for RestoreConfig {
+            fn drop(&mut self) {
+                set_blocking_config(BlockingConfig::default());
+            }
+        }
+        let _restore = RestoreConfig;
+
+        set_blocking_config(BlockingConfig {
+            max_threads: 4,
+            keep_alive: Duration::from_millis(50),
+        });
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                spawn_blocking(move || {
+                    sleep(Duration::from_millis(20));
+                    i * 2
+                })
+            })
+            .collect();
+        assert!(blocking_threads() <= 4);
+        let r: Vec<_> = handles.into_iter().map(|h| h.join()).collect();
+        assert_eq!(r, (0..8).map(|i| i * 2).collect::<Vec<_>>());
+
+        let handle = spawn_blocking(|| panic!("blocking panic"));
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| handle.join())).is_err());
+
+        // 阻塞期间 worker 继续执行其它 job
+        let count = AtomicUsize::new(0);
+        let v: Vec<usize> = (0..64usize)
+            .into_par_iter()
+            .map(|i| {
+                block_in_place(|| {
+                    sleep(Duration::from_millis(5));
+                    count.fetch_add(1, Ordering::Relaxed);
+                });
+                i
+            })
+            .collect();
+        assert_eq!(v, (0..64).collect::<Vec<_>>());
+        assert_eq!(count.load(Ordering::Relaxed), 64);
+
+        // 空闲的线程在 keep_alive 之后退出
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while blocking_threads() != 0 && Instant::now() < deadline {
+            sleep(Duration::from_millis(10));
+        }
+        assert_eq!(blocking_threads(), 0);
+    }
+
+    #[test]
+    fn thread_pool_resize() {
+        let pool = ThreadPool::new(2);
+        let sum = |pool: &ThreadPool| {
+            let v: Vec<u64> =
+                pool.install(|| (0..10_000u64).into_par_iter().map(|x| x * 2).collect());
+            v.iter().sum::<u64>()
+        };
+
+        pool.resize(6);
+        assert_eq!(pool.current_num_threads(), 6);
+        assert_eq!(pool.install(current_num_threads), 6);
+        assert_eq!(sum(&pool), 9_999 * 10_000);
+
+        pool.resize(1);
+        assert_eq!(pool.current_num_threads(), 1);
+        assert_eq!(sum(&pool), 9_999 * 10_000);
+
+        // 多出的 worker 退出后，所有的 job 都在剩下的一个 worker 上执行
+        sleep(Duration::from_millis(50));
+        let ids = std::sync::Mutex::new(HashSet::new());
+        pool.install(|| {
+            (0..1000u32).into_par_iter().for_each(|_| {
+                ids.lock().unwrap().insert(thread::current().id());
+            })
+        });
+        assert_eq!(ids.lock().unwrap().len(), 1);
+
+        pool.resize(3);
+        assert_eq!(pool.current_num_threads(), 3);
+        assert_eq!(sum(&pool), 9_999 * 10_000);
+
+        // 缩容后马上 drop：Retiring 的 worker 依然执行完自己队列中的 job 再退出
+        let started = std::sync::Arc::new(std::sync::Barrier::new(4));
+        let release = std::sync::Arc::new(std::sync::Barrier::new(4));
+        let (tx, rx) = std::sync::mpsc::channel();
+        for i in 0..3 {
+            let (started, release, tx) = (started.clone(), release.clone(), tx.clone());
+            pool.spawn_with_priority(Priority::Normal, move || {
+                // 在 worker 上 spawn 的 job 放在这个 worker 自己的队列中
+                for j in 0..10 {
+                    let tx = tx.clone();
+                    spawn(move || tx.send(i * 10 + j).unwrap());
+                }
+                started.wait();
+                release.wait();
+            });
+        }
+        drop(tx);
+        started.wait();
+        pool.resize(1);
+        drop(pool);
+        release.wait();
+
+        let mut received = Vec::new();
+        while let Ok(i) = rx.recv_timeout(Duration::from_secs(10)) {
+            received.push(i);
+        }
+        received.sort();
+        assert_eq!(received, (0..30).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn default_threads() {
+        let n = default_num_threads();
+        assert!(n >= 1);
+        assert!(n <= thread::available_parallelism().unwrap().get());
+        assert_eq!(ThreadPool::default().current_num_threads(), n);
+
+        // 只测试解析，不修改进程的环境变量
+        assert_eq!(parse_num_threads("3"), Some(3));
+        assert_eq!(parse_num_threads(" 8\n"), Some(8));
+        // 非法的值被忽略
+        assert_eq!(parse_num_threads("0"), None);
+        assert_eq!(parse_num_threads("-1"), None);
+        assert_eq!(parse_num_threads("four"), None);
+        assert_eq!(parse_num_threads(""), None);
+    }
+
+    #[test]
+    fn worker_local() {
+        use std::cell::RefCell;
+
+        let pool = ThreadPool::new(4);
+        let data: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+
+        // 每个 worker 一个直方图，不需要加锁
+        let mut histogram = pool.install(|| {
+            let histogram = WorkerLocal::new(|| RefCell::new([0usize; 256]));
+            data.par_iter()
+                .for_each(|&b| histogram.get().borrow_mut()[b as usize] += 1);
+            histogram
+        });
+
+        let mut merged = [0usize; 256];
+        for local in histogram.iter_mut() {
+            for (m, c) in merged.iter_mut().zip(local.get_mut().iter()) {
+                *m += c;
+            }
+        }
+        let mut expected = [0usize; 256];
+        data.iter().for_each(|&b| expected[b as usize] += 1);
+        assert_eq!(merged, expected);
+        assert_eq!(histogram.into_inner().len(), 4);
+
+        // 扩容新增的 worker 第一次 get 时创建自己的 T
+        let pool = ThreadPool::new(1);
+        let local = pool.install(|| WorkerLocal::new(|| RefCell::new(0usize)));
+        pool.resize(2);
+        let barrier = std::sync::Barrier::new(2);
+        // 两边都等在 barrier 上，b 只能被新的 worker 窃取执行
+        pool.install(|| {
+            join(
+                || {
+                    barrier.wait();
+                    *local.get().borrow_mut() += 1;
+                },
+                || {
+                    barrier.wait();
+                    *local.get().borrow_mut() += 1;
+                },
+            )
+        });
+        let counts: Vec<_> = local
+            .into_inner()
+            .into_iter()
+            .map(|c| c.into_inner())
+            .collect();
+        assert_eq!(counts, vec![1, 1]);
+
+        let local = WorkerLocal::new(|| 1);
+        let local = std::panic::AssertUnwindSafe(&local);
+        assert!(std::panic::catch_unwind(|| *local.get()).is_err());
+        assert!(pool
+            .install(|| std::panic::catch_unwind(|| *local.get()))
+            .is_err());
+
+        // 其它线程看不到 worker 的 !Sync 的 T：worker 持有 borrow_mut 时 Debug 只输出槽位数，get 会 panic
+        let pool = ThreadPool::new(2);
+        let local = pool.install(|| WorkerLocal::new(|| RefCell::new(0u32)));
+        let held = std::sync::Barrier::new(2);
+        let release = std::sync::Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                pool.install(|| {
+                    let mut value = local.get().borrow_mut();
+                    *value += 1;
+                    held.wait();
+                    release.wait();
+                })
+            });
+
+            held.wait();
+            assert_eq!(format!("{:?}", local), "WorkerLocal { len: 2 }");
+            let local = std::panic::AssertUnwindSafe(&local);
+            assert!(std::panic::catch_unwind(|| *local.get().borrow()).is_err());
+            release.wait();
+        });
+        drop(pool);
+
+        // 线程池释放后新的线程池即使复用了同一个地址，也不能访问旧线程池的 WorkerLocal
+        let pool = ThreadPool::new(2);
+        let local = std::panic::AssertUnwindSafe(&local);
+        assert!(pool
+            .install(|| std::panic::catch_unwind(|| *local.get().borrow()))
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn task_context_propagation() {
+        use concurrent_threads::{task_context, with_task_context};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        assert_eq!(task_context::<u32>(), None);
+
+        // 被其它 worker 窃取的叶子也能拿到提交方的上下文
+        let seen = AtomicUsize::new(0);
+        with_task_context(42u32, || {
+            (0..10_000u32).into_par_iter().for_each(|_| {
+                if task_context::<u32>().as_deref() == Some(&42) {
+                    seen.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+            assert_eq!(task_context::<String>(), None);
+        });
+        assert_eq!(seen.load(Ordering::Relaxed), 10_000);
+        assert_eq!(task_context::<u32>(), None);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        with_task_context("request-1", || {
+            spawn(move || tx.send(task_context::<&str>().map(|v| *v)).unwrap())
+        });
+        assert_eq!(rx.recv().unwrap(), Some("request-1"));
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn span_propagation() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tracing::{dispatcher, info_span, Span};
+        use tracing_subscriber::{registry::LookupSpan, Registry};
+
+        // Registry 不输出日志，只记录 span 的父子关系，worker 线程也要能看到，所以设置为全局的
+        let _ = tracing::subscriber::set_global_default(Registry::default());
+
+        let pool = ThreadPool::new(4);
+        let seen = AtomicUsize::new(0);
+        let stolen = AtomicUsize::new(0);
+
+        pool.install(|| {
+            let span = info_span!("submit");
+            let _entered = span.enter();
+            let id = span.id().unwrap();
+            let submitter = thread::current().id();
+
+            // 叶子中的当前 span 可能是 join 的 span，提交方的 span 是它的祖先
+            (0..1000u32).into_par_iter().for_each(|_| {
+                sleep(Duration::from_micros(100));
+
+                let inside = dispatcher::get_default(|dispatch| {
+                    let registry = dispatch.downcast_ref::<Registry>().unwrap();
+                    Span::current()
+                        .id()
+                        .and_then(|current| registry.span(&current))
+                        .is_some_and(|current| current.scope().any(|s| s.id() == id))
+                });
+
+                if inside {
+                    seen.fetch_add(1, Ordering::Relaxed);
+                }
+                if thread::current().id() != submitter {
+                    stolen.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        });
+
+        assert_eq!(seen.load(Ordering::Relaxed), 1000);
+        assert!(stolen.load(Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn deterministic_schedule() {
+        use concurrent_threads::for_each_seed;
+        use std::sync::Mutex;
+
+        fn record(depth: u32, id: u32, order: &Mutex<Vec<u32>>) {
+            if depth == 0 {
+                order.lock().unwrap().push(id);
+                return;
+            }
+            join(
+                || record(depth - 1, id * 2, order),
+                || record(depth - 1, id * 2 + 1, order),
+            );
+        }
+
+        let schedule = |seed| {
+            let pool = ThreadPool::deterministic(4, seed);
+            let order = Mutex::new(Vec::new());
+            pool.install(|| record(6, 0, &order));
+            order.into_inner().unwrap()
+        };
+
+        // 同一个 seed 重放同样的执行顺序，不同的 seed 得到不同的执行顺序
+        assert_eq!(schedule(7), schedule(7));
+        assert_ne!(schedule(7), schedule(8));
+
+        let pool = ThreadPool::deterministic(4, 1);
+        assert_eq!(pool.install(current_num_threads), 4);
+
+        for_each_seed(0..32, |_| {
+            let v: Vec<u32> = (0..1000u32).into_par_iter().map(|x| x + 1).collect();
+            assert_eq!(v, (1..=1000).collect::<Vec<_>>());
+        });
+
+        let r = std::panic::catch_unwind(|| {
+            for_each_seed(0..32, |seed| assert_ne!(seed, 5, "bad seed"));
+        });
+        let err = r.unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("seed 5"), "{msg}");
+    }
 }
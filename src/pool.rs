@@ -0,0 +1,241 @@
+use std::{
+    error::Error,
+    fmt, mem,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::Duration,
+};
+
+use tracing::{instrument, trace};
+
+use crate::{
+    job::{HeapJob, Job, JobRef, JobResult},
+    latch::Latch,
+    thread_data::{default_num_threads, Priority, Root},
+    ThreadWoker,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout;
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("operation timed out")
+    }
+}
+
+impl Error for Timeout {}
+
+// 独立于全局线程池的线程池，drop 时通知所有 worker 在执行完剩余的 job 后退出，drop 本身不等待
+pub struct ThreadPool {
+    root: Arc<Root>,
+}
+
+impl ThreadPool {
+    pub fn new(num_threads: usize) -> Self {
+        assert!(num_threads > 0, "num_threads must not be zero");
+
+        let root = Root::new(num_threads);
+        root.wait_thread_created();
+
+        ThreadPool { root }
+    }
+
+    // 用于测试的确定性线程池：所有 job 串行执行，执行顺序完全由 seed 决定，同一个 seed 可以重放同样的执行顺序
+    // num_threads 只影响 current_num_threads 的返回值，也就是并行迭代器的拆分方式
+    pub fn deterministic(num_threads: usize, seed: u64) -> Self {
+        assert!(num_threads > 0, "num_threads must not be zero");
+
+        let root = Root::new_deterministic(num_threads, seed);
+        root.wait_thread_created();
+
+        ThreadPool { root }
+    }
+
+    pub fn current_num_threads(&self) -> usize {
+        self.root.num_threads()
+    }
+
+    // 调整 worker 数：扩容时等待新的 worker 创建完成；缩容时多出的 worker 执行完自己队列中的 job 后退出，
+    // 正在执行的 job 不受影响
+    pub fn resize(&self, num_threads: usize) {
+        assert!(num_threads > 0, "num_threads must not be zero");
+        assert!(
+            self.root.scheduler.is_none(),
+            "cannot resize a deterministic pool"
+        );
+
+        self.root.resize(num_threads);
+    }
+
+    // 在线程池中执行 op，op 中的 join / 并行迭代器都使用这个线程池
+    #[instrument(skip_all)]
+    pub fn install<OP, R>(&self, op: OP) -> R
+    where
+        OP: FnOnce() -> R + Send,
+        R: Send,
+    {
+        if self.is_current() {
+            return op();
+        }
+
+        install_in(&self.root, Priority::Normal, op)
+    }
+
+    // 和 install 一样，但是调用方最多等待 timeout，超时后返回 Err(Timeout)
+    // 超时后 op 依然会在线程池中执行完，结果保存在堆上的共享状态中，所以 op 和结果都必须是 'static 的
+    // 在线程池内部调用时直接执行 op，不会超时
+    #[instrument(skip_all)]
+    pub fn install_timeout<OP, R>(&self, op: OP, timeout: Duration) -> Result<R, Timeout>
+    where
+        OP: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        if self.is_current() {
+            return Ok(op());
+        }
+
+        let shared = inject_shared(&self.root, op);
+
+        if shared.latch.wait_timeout(timeout) {
+            Ok(shared.take())
+        } else {
+            trace!("install timeout");
+            Err(Timeout)
+        }
+    }
+
+    // 在线程池中异步执行 func，不等待结果，worker 空闲时优先执行高优先级的 job
+    #[instrument(skip_all)]
+    pub fn spawn_with_priority<F>(&self, priority: Priority, func: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        unsafe {
+            self.root
+                .inject(HeapJob::new(func).into_job_ref(), priority);
+        }
+    }
+
+    fn is_current(&self) -> bool {
+        let worker = ThreadWoker::current();
+
+        !worker.is_null() && unsafe { Arc::ptr_eq(&(*worker).root, &self.root) }
+    }
+}
+
+// 线程数和全局线程池一样由 default_num_threads 决定
+impl Default for ThreadPool {
+    fn default() -> Self {
+        ThreadPool::new(default_num_threads())
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.root.state.terminated.store(true, Ordering::Release);
+    }
+}
+
+fn install_in<OP, R>(root: &Root, priority: Priority, op: OP) -> R
+where
+    OP: FnOnce() -> R + Send,
+    R: Send,
+{
+    unsafe {
+        let latch = Arc::new(Latch::new());
+        let mut result = JobResult::None;
+        let job = Job::new(op, latch.clone(), &mut result);
+
+        root.inject(JobRef::new(&job), priority);
+        latch.wait();
+
+        result.into_return_value()
+    }
+}
+
+// 在全局线程池中以指定的优先级执行 op 并等待结果，已经在 worker 上时直接执行
+#[instrument(skip_all)]
+pub fn install_with_priority<OP, R>(priority: Priority, op: OP) -> R
+where
+    OP: FnOnce() -> R + Send,
+    R: Send,
+{
+    if !ThreadWoker::current().is_null() {
+        return op();
+    }
+
+    install_in(Root::current(), priority, op)
+}
+
+// 结果保存在堆上，调用方放弃等待后 job 依然可以安全地写入结果
+struct SharedResult<R> {
+    latch: Latch,
+    result: Mutex<JobResult<R>>,
+}
+
+impl<R> SharedResult<R> {
+    fn take(&self) -> R {
+        mem::replace(&mut *self.result.lock().unwrap(), JobResult::None).into_return_value()
+    }
+}
+
+fn inject_shared<F, R>(root: &Root, func: F) -> Arc<SharedResult<R>>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let shared = Arc::new(SharedResult {
+        latch: Latch::new(),
+        result: Mutex::new(JobResult::None),
+    });
+    let job_shared = shared.clone();
+
+    let job = HeapJob::new(move || {
+        let result = match catch_unwind(AssertUnwindSafe(func)) {
+            Ok(r) => JobResult::Ok(r),
+            Err(err) => JobResult::Panic(err),
+        };
+
+        *job_shared.result.lock().unwrap() = result;
+        job_shared.latch.set();
+    });
+
+    unsafe {
+        root.inject(job.into_job_ref(), Priority::Normal);
+    }
+
+    shared
+}
+
+// 和 join 一样并行执行 a / b，线程池外部的调用方最多等待 timeout，超时后返回 Err(Timeout)
+// 在线程池内部调用时直接 join，不会超时
+#[instrument(skip_all)]
+pub fn join_timeout<F1, F2, R1, R2>(a: F1, b: F2, timeout: Duration) -> Result<(R1, R2), Timeout>
+where
+    F1: FnOnce() -> R1 + Send + 'static,
+    F2: FnOnce() -> R2 + Send + 'static,
+    R1: Send + 'static,
+    R2: Send + 'static,
+{
+    if !ThreadWoker::current().is_null() {
+        return Ok(crate::join(a, b));
+    }
+
+    let root = Root::current();
+    let result_a = inject_shared(root, a);
+    let result_b = inject_shared(root, b);
+
+    // 两个 job 共享同一个截止时间
+    let start = std::time::Instant::now();
+    if !result_a.latch.wait_timeout(timeout)
+        || !result_b
+            .latch
+            .wait_timeout(timeout.saturating_sub(start.elapsed()))
+    {
+        trace!("join timeout");
+        return Err(Timeout);
+    }
+
+    Ok((result_a.take(), result_b.take()))
+}
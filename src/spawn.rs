@@ -0,0 +1,146 @@
+use std::{
+    future::Future,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use tracing::{instrument, trace};
+
+use crate::{
+    job::HeapJob,
+    thread_data::{Priority, Root},
+    ThreadWoker,
+};
+
+// 把 job 放入线程池：在 worker 上时普通优先级的 job 放入当前 worker 的队列，否则放入对应优先级的全局队列
+fn inject_heap_job<F>(priority: Priority, func: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    let worker = ThreadWoker::current();
+
+    unsafe {
+        let job = HeapJob::new(func).into_job_ref();
+
+        if worker.is_null() {
+            trace!("spawn into root: {:?}", priority);
+            Root::current().inject(job, priority);
+        } else if priority == Priority::Normal {
+            trace!("spawn on worker: {}", (*worker).index);
+            (*worker).push(job);
+        } else {
+            trace!("spawn into root: {:?}", priority);
+            (*worker).root.inject(job, priority);
+        }
+    }
+}
+
+// 在线程池中异步执行 func，不等待结果，func 中的 panic 会被捕获并记录
+#[instrument(skip_all)]
+pub fn spawn<F>(func: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    inject_heap_job(Priority::Normal, func);
+}
+
+// 和 spawn 一样，worker 空闲时优先执行高优先级的 job
+#[instrument(skip_all)]
+pub fn spawn_with_priority<F>(priority: Priority, func: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    inject_heap_job(priority, func);
+}
+
+// 在线程池中执行 func，返回的 future 在 func 结束时被唤醒，不会阻塞调用方的线程
+// func panic 时，panic 会在 await 返回的 future 时重新抛出
+#[instrument(skip_all)]
+pub fn spawn_future<F, R>(func: F) -> SpawnFuture<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let oneshot = Arc::new(Mutex::new(Oneshot::default()));
+    let sender = oneshot.clone();
+
+    inject_heap_job(Priority::Normal, move || {
+        let result = catch_unwind(AssertUnwindSafe(func));
+
+        let waker = {
+            let mut oneshot = sender.lock().unwrap();
+            oneshot.result = Some(result);
+            oneshot.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+
+    SpawnFuture { oneshot }
+}
+
+// 和 spawn_future 一样在线程池中执行 op，op 中的 join / 并行迭代器都直接在 worker 上执行；
+// 已经在 worker 上时直接执行 op，返回一个已经完成的 future
+#[instrument(skip_all)]
+pub fn install_async<OP, R>(op: OP) -> SpawnFuture<R>
+where
+    OP: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    if ThreadWoker::current().is_null() {
+        return spawn_future(op);
+    }
+
+    let oneshot = Oneshot {
+        result: Some(catch_unwind(AssertUnwindSafe(op))),
+        waker: None,
+    };
+
+    SpawnFuture {
+        oneshot: Arc::new(Mutex::new(oneshot)),
+    }
+}
+
+struct Oneshot<R> {
+    result: Option<thread::Result<R>>,
+    waker: Option<Waker>,
+}
+
+impl<R> Default for Oneshot<R> {
+    fn default() -> Self {
+        Oneshot {
+            result: None,
+            waker: None,
+        }
+    }
+}
+
+pub struct SpawnFuture<R> {
+    oneshot: Arc<Mutex<Oneshot<R>>>,
+}
+
+impl<R> Future for SpawnFuture<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut oneshot = self.oneshot.lock().unwrap();
+
+        match oneshot.result.take() {
+            Some(Ok(r)) => Poll::Ready(r),
+            Some(Err(err)) => {
+                drop(oneshot);
+                resume_unwind(err)
+            }
+            None => {
+                // 每次 poll 都更新 waker，future 可能在不同的 task 之间移动
+                oneshot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -1,19 +1,23 @@
 use std::{
     collections::VecDeque,
-    sync::{Arc, Mutex, Once},
+    env, fs,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, Once, RwLock,
+    },
     thread,
 };
 
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
-use crate::{util::leak, JobRef, ThreadData, ThreadWoker};
+use crate::{deterministic::Scheduler, util::leak, JobRef, ThreadData, ThreadWoker, WorkerState};
 
 static mut ROOT: Option<&'static Root> = None;
 static ROOT_SET: Once = Once::new();
 
 fn initialize() -> &'static Root {
     ROOT_SET.call_once(|| unsafe {
-        let root = Root::new();
+        let root = Root::new(default_num_threads());
         let root = leak(root);
         ROOT = Some(root);
     });
@@ -25,31 +29,122 @@ fn initialize() -> &'static Root {
     root
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+}
+
+// 每从全局队列中取出 STARVATION_TICKS 个 job 有一次从低优先级开始查找，保证高优先级的任务源源不断时低优先级的任务也能执行
+const STARVATION_TICKS: usize = 16;
+
 #[derive(Default)]
 pub struct RootState {
-    pub pending_tasks_job: Mutex<VecDeque<JobRef>>,
+    // 按 Priority 的顺序，每个优先级一个队列
+    pub pending_tasks_job: [Mutex<VecDeque<JobRef>>; 3],
+    tick: AtomicUsize,
+    // 线程池被 drop 后设置，worker 执行完全局队列和自己队列中剩余的 job 后退出
+    pub terminated: AtomicBool,
 }
 
+static NEXT_ROOT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// threads 中只有 Live 和 Retiring 的 worker，worker 退出时把自己移除，index 在线程池中唯一，不会复用
 pub struct Root {
-    pub threads: Vec<ThreadData>,
+    // 线程池的唯一标识，不会复用；Root 释放后地址可能被新的 Root 复用，不能用地址区分线程池
+    pub id: usize,
+    pub threads: RwLock<Vec<Arc<ThreadData>>>,
     pub state: RootState,
+    next_index: AtomicUsize,
+    // 确定性调度的线程池才有
+    pub scheduler: Option<Scheduler>,
+}
+
+const NUM_THREADS_ENV: &str = "CONCURRENT_THREADS_NUM";
+
+// 默认的线程数：优先使用环境变量 CONCURRENT_THREADS_NUM，其次是 cgroup 的 CPU 配额，最后是 available_parallelism
+// 容器中 available_parallelism 可能是宿主机的核数，按配额取较小的值，避免线程数远多于可用的 CPU
+pub fn default_num_threads() -> usize {
+    if let Ok(value) = env::var(NUM_THREADS_ENV) {
+        match parse_num_threads(&value) {
+            Some(n) => return n,
+            None => warn!("invalid {}: {:?}", NUM_THREADS_ENV, value),
+        }
+    }
+
+    let parallelism = thread::available_parallelism().map_or(1, |n| n.get());
+
+    match cgroup_cpu_quota() {
+        Some(quota) => quota.min(parallelism),
+        None => parallelism,
+    }
+}
+
+// 解析 CONCURRENT_THREADS_NUM 的值，不是正整数时返回 None
+pub fn parse_num_threads(value: &str) -> Option<usize> {
+    match value.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Some(n),
+        _ => None,
+    }
 }
 
-pub const CORE_NUMS: usize = 8;
+// cgroup v2 的 cpu.max 是 "$MAX $PERIOD"，没有限制时 $MAX 是 "max"；
+// cgroup v1 是 cpu.cfs_quota_us 和 cpu.cfs_period_us，没有限制时 quota 是 -1
+fn cgroup_cpu_quota() -> Option<usize> {
+    if let Ok(content) = fs::read_to_string("/sys/fs/cgroup/cpu.max") {
+        let mut fields = content.split_whitespace();
+        let quota = fields.next()?.parse().ok()?;
+        let period = fields.next()?.parse().ok()?;
+
+        return cpu_quota(quota, period);
+    }
+
+    ["/sys/fs/cgroup/cpu", "/sys/fs/cgroup/cpu,cpuacct"]
+        .iter()
+        .find_map(|dir| {
+            let read = |name| fs::read_to_string(format!("{dir}/{name}")).ok();
+            let quota = read("cpu.cfs_quota_us")?.trim().parse().ok()?;
+            let period = read("cpu.cfs_period_us")?.trim().parse().ok()?;
+
+            cpu_quota(quota, period)
+        })
+}
+
+// 配额不足一个 CPU 时向上取整为 1
+fn cpu_quota(quota: i64, period: i64) -> Option<usize> {
+    if quota <= 0 || period <= 0 {
+        return None;
+    }
+
+    Some(((quota + period - 1) / period) as usize)
+}
 
 impl Root {
-    fn new() -> Arc<Root> {
+    pub fn new(num_threads: usize) -> Arc<Root> {
+        Self::with_scheduler(num_threads, None)
+    }
+
+    // 只有一个真正的 worker，job 的执行顺序由 seed 决定
+    pub fn new_deterministic(num_threads: usize, seed: u64) -> Arc<Root> {
+        Self::with_scheduler(1, Some(Scheduler::new(seed, num_threads)))
+    }
+
+    fn with_scheduler(num_threads: usize, scheduler: Option<Scheduler>) -> Arc<Root> {
         let root = Arc::new(Root {
-            threads: (0..CORE_NUMS).map(ThreadData::new).collect(),
+            id: NEXT_ROOT_ID.fetch_add(1, Ordering::Relaxed),
+            threads: RwLock::new(Vec::new()),
             state: RootState::default(),
+            next_index: AtomicUsize::new(0),
+            scheduler,
         });
 
-        for index in 0..CORE_NUMS {
-            let thread_root = root.clone();
-            thread::spawn(move || {
-                thread_loop(index, thread_root);
-            });
-        }
+        root.spawn_workers(num_threads);
 
         root
     }
@@ -58,45 +153,129 @@ impl Root {
         initialize()
     }
 
-    fn wait_thread_created(&self) {
-        for thread in &self.threads {
+    fn spawn_workers(self: &Arc<Self>, num_threads: usize) {
+        let mut threads = self.threads.write().unwrap();
+
+        for _ in 0..num_threads {
+            let index = self.next_index.fetch_add(1, Ordering::Relaxed);
+            let data = Arc::new(ThreadData::new(index));
+            threads.push(data.clone());
+
+            let thread_root = self.clone();
+            thread::spawn(move || {
+                thread_loop(data, thread_root);
+            });
+        }
+    }
+
+    // 已经分配过的 worker index 都小于这个值
+    pub fn index_bound(&self) -> usize {
+        self.next_index.load(Ordering::Relaxed)
+    }
+
+    pub fn wait_thread_created(&self) {
+        for thread in self.threads.read().unwrap().iter() {
             thread.wait();
             trace!("thread created: {}", thread.index);
         }
     }
 
+    // 当前 Live 的 worker 数，Retiring 的 worker 不再计入
+    pub fn num_threads(&self) -> usize {
+        if let Some(scheduler) = &self.scheduler {
+            return scheduler.num_threads();
+        }
+
+        self.threads
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|thread| thread.state() == WorkerState::Live)
+            .count()
+    }
+
+    // 扩容时创建新的 worker；缩容时把最后创建的 worker 标记为 Retiring，它们执行完自己队列中的 job 后退出
+    pub fn resize(self: &Arc<Self>, num_threads: usize) {
+        let live = self.num_threads();
+
+        if num_threads > live {
+            debug!("resize: add {} workers", num_threads - live);
+            self.spawn_workers(num_threads - live);
+            self.wait_thread_created();
+        } else if num_threads < live {
+            debug!("resize: retire {} workers", live - num_threads);
+            self.threads
+                .read()
+                .unwrap()
+                .iter()
+                .rev()
+                .filter(|thread| thread.state() == WorkerState::Live)
+                .take(live - num_threads)
+                .for_each(|thread| thread.set_state(WorkerState::Retiring));
+        }
+    }
+
+    pub fn inject(&self, job: JobRef, priority: Priority) {
+        self.state.pending_tasks_job[priority as usize]
+            .lock()
+            .unwrap()
+            .push_back(job);
+    }
+
+    // 先从全局队列中取，没有时从其它 worker 的队列中窃取
+    pub fn find_work(&self, index: usize) -> Option<JobRef> {
+        self.wait_task().or_else(|| self.steal(index))
+    }
+
     fn wait_task(&self) -> Option<JobRef> {
-        loop {
-            let mut pending_tasks = self.state.pending_tasks_job.lock().unwrap();
+        // tick 只在真正取到 job 时增加，空闲时的轮询不计入，比例只和执行的 job 数有关
+        let tick = self.state.tick.load(Ordering::Relaxed);
+        let mut order = Priority::ALL;
 
-            trace!("worker {} wait a job {}", ThreadWoker::current_index(), pending_tasks.len());
-            if pending_tasks.is_empty() {
-                break;
-            }
+        if tick % STARVATION_TICKS == STARVATION_TICKS - 1 {
+            order.reverse();
+        }
 
-            if let Some(job) = pending_tasks.pop_front() {
-                trace!("worker {} take a job", ThreadWoker::current_index());
-                return Some(job);
+        order.into_iter().find_map(|priority| {
+            let mut queue = self.state.pending_tasks_job[priority as usize]
+                .lock()
+                .unwrap();
+            let job = match &self.scheduler {
+                Some(scheduler) if !queue.is_empty() => {
+                    let index = scheduler.pick(queue.len());
+                    queue.remove(index)
+                }
+                _ => queue.pop_front(),
             };
+            drop(queue);
 
-            thread::yield_now();
-        }
+            if job.is_some() {
+                self.state.tick.fetch_add(1, Ordering::Relaxed);
+                trace!(
+                    "worker {} take a {:?} job",
+                    ThreadWoker::current_index(),
+                    priority
+                );
+            }
 
-        None
+            job
+        })
     }
 
+    // 确定性调度的线程池只有一个 worker，不会走到真正的窃取，见 Scheduler::swap_join
     fn steal(&self, index: usize) -> Option<JobRef> {
         self.threads
+            .read()
+            .unwrap()
             .iter()
-            .enumerate()
-            .filter(|(i, _)| *i != index)
-            .find_map(|(thread_index, thread)| {
+            .filter(|thread| thread.index != index)
+            .find_map(|thread| {
                 if let Ok(mut v) = thread.queue.try_lock() {
                     if v.is_empty() {
                         return None;
                     }
 
-                    debug!("worker {} steal a job from worker {}", index, thread_index);
+                    debug!("worker {} steal a job from worker {}", index, thread.index);
 
                     return v.pop_front();
                 }
@@ -106,23 +285,43 @@ impl Root {
     }
 }
 
-fn thread_loop(index: usize, root: Arc<Root>) {
+fn thread_loop(data: Arc<ThreadData>, root: Arc<Root>) {
+    let index = data.index;
     let worker = ThreadWoker {
         root: root.clone(),
+        data: data.clone(),
         index,
     };
 
     worker.set_current();
 
-    root.threads[index].crated.set();
+    data.crated.set();
 
     loop {
         trace!("worker {} loop", index);
-        // root.threads
-        if let Some(job) = root.wait_task() {
-            unsafe { job.execute() };
-        } else if let Some(job) = root.steal(index) {
+
+        // Retiring 的 worker 不再领取新的 job，执行完自己队列中剩余的 job 后退出，线程池被 drop 后也一样；
+        // 队列中 join 的 job_b 会被等待它的 worker 在 join 中自己弹出执行，不会被遗漏
+        if data.state() == WorkerState::Retiring {
+            while let Some(job) = worker.pop() {
+                unsafe { job.execute() };
+            }
+
+            let mut threads = root.threads.write().unwrap();
+            data.set_state(WorkerState::Exited);
+            threads.retain(|thread| !Arc::ptr_eq(thread, &data));
+            debug!("worker {} exited", index);
+
+            return;
+        }
+
+        // 线程池被 drop 后依然执行完所有剩余的 job 再退出：job 中捕获的值（例如 Sender）必须被 drop，
+        // 超时的 install_timeout / join_timeout 的 job 也要执行完
+        if let Some(job) = worker.pop().or_else(|| root.find_work(index)) {
             unsafe { job.execute() };
+        } else if root.state.terminated.load(Ordering::Acquire) {
+            debug!("worker {} exited after the pool was dropped", index);
+            return;
         }
 
         thread::yield_now();
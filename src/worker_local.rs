@@ -0,0 +1,124 @@
+use std::{fmt, sync::OnceLock};
+
+use crate::{thread_data::Root, ThreadWoker};
+
+// 分块的数量，第 k 块有 2^k 个槽位，合起来覆盖所有的 usize 下标
+const CHUNKS: usize = usize::BITS as usize;
+
+// 每个 worker 一个 T，按 ThreadWoker::index 存放，每个 T 只会被对应的 worker 通过 get 访问，
+// 所以 T 只需要 Send；并行阶段结束后用 iter_mut / into_inner 合并结果
+// 通过 &WorkerLocal 只能用 get 访问当前 worker 自己的 T，Debug 也只输出槽位数，不读取其它 worker 的 T
+// 只属于创建时所在的线程池，在其它线程池或者线程池外部调用 get 会 panic
+// 扩容新增的 worker 第一次调用 get 时才创建它的 T，已有的槽位不会移动，get 返回的引用一直有效
+pub struct WorkerLocal<T> {
+    chunks: [Chunk<T>; CHUNKS],
+    init: Box<dyn Fn() -> T + Send + Sync>,
+    root_id: usize,
+}
+
+type Chunk<T> = OnceLock<Box<[OnceLock<CacheAligned<T>>]>>;
+
+// 对齐到缓存行，避免相邻 worker 的数据互相影响
+#[repr(align(64))]
+struct CacheAligned<T>(T);
+
+unsafe impl<T: Send> Send for WorkerLocal<T> {}
+unsafe impl<T: Send> Sync for WorkerLocal<T> {}
+
+// index 所在的块和块内的偏移：index + 1 的最高位决定块
+fn locate(index: usize) -> (usize, usize) {
+    let chunk = (usize::BITS - 1 - (index + 1).leading_zeros()) as usize;
+    (chunk, index + 1 - (1 << chunk))
+}
+
+impl<T> WorkerLocal<T> {
+    // 为当前线程池（不在 worker 上时是全局线程池）现有的每个 worker 调用一次 init
+    // 之后扩容新增的 worker 在第一次 get 时调用 init
+    pub fn new<F>(init: F) -> Self
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        let worker = ThreadWoker::current();
+        let root: &Root = if worker.is_null() {
+            Root::current()
+        } else {
+            unsafe { &(*worker).root }
+        };
+
+        let local = WorkerLocal {
+            chunks: std::array::from_fn(|_| OnceLock::new()),
+            init: Box::new(init),
+            root_id: root.id,
+        };
+
+        for index in 0..root.index_bound() {
+            local
+                .slot(index)
+                .get_or_init(|| CacheAligned((local.init)()));
+        }
+
+        local
+    }
+
+    fn slot(&self, index: usize) -> &OnceLock<CacheAligned<T>> {
+        let (chunk, offset) = locate(index);
+        let chunk =
+            self.chunks[chunk].get_or_init(|| (0..1 << chunk).map(|_| OnceLock::new()).collect());
+
+        &chunk[offset]
+    }
+
+    pub fn get(&self) -> &T {
+        let worker = ThreadWoker::current();
+        assert!(
+            !worker.is_null(),
+            "WorkerLocal::get called outside of the pool"
+        );
+
+        let worker = unsafe { &*worker };
+        assert!(
+            worker.root.id == self.root_id,
+            "WorkerLocal::get called from a different pool"
+        );
+
+        &self
+            .slot(worker.index)
+            .get_or_init(|| CacheAligned((self.init)()))
+            .0
+    }
+
+    // 按 worker 的下标顺序返回已经创建的 T
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.chunks
+            .iter_mut()
+            .filter_map(|chunk| chunk.get_mut())
+            .flat_map(|chunk| chunk.iter_mut())
+            .filter_map(|slot| slot.get_mut())
+            .map(|local| &mut local.0)
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.chunks
+            .into_iter()
+            .filter_map(|chunk| chunk.into_inner())
+            .flat_map(|chunk| chunk.into_vec())
+            .filter_map(|slot| slot.into_inner())
+            .map(|local| local.0)
+            .collect()
+    }
+}
+
+// T 可能不是 Sync（例如 RefCell），其它 worker 可能正在修改自己的 T，这里不能读取 T 的内容
+impl<T> fmt::Debug for WorkerLocal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self
+            .chunks
+            .iter()
+            .filter_map(|chunk| chunk.get())
+            .flat_map(|chunk| chunk.iter())
+            .filter(|slot| slot.get().is_some())
+            .count();
+
+        f.debug_struct("WorkerLocal").field("len", &len).finish()
+    }
+}